@@ -1,87 +1,598 @@
-use crate::sprite::Sprite;
-use std::collections::HashMap;
-
-pub struct Animation {
-    pub frames: Vec<Sprite>,
-    pub speed: f32,
-    pub looped: bool,
-    pub current: usize,
-}
-
-pub struct AnimationHandler {
-    animations: HashMap<String, Animation>,
-    pub current_state: String,
-    pub frame_counter: f32,
-}
-
-impl AnimationHandler {
-    pub fn new() -> Self {
-        Self {
-            animations: HashMap::with_capacity(16),
-            current_state: String::new(),
-            frame_counter: 0.0,
-        }
-    }
-    
-    pub fn add_animation(&mut self, name: &str, frames: Vec<Sprite>, speed: f32, looped: bool) {
-        self.animations.insert(name.to_string(), Animation {
-            frames, speed, looped, current: 0,
-        });
-    }
-    
-    pub fn set_state(&mut self, state: &str) {
-        if self.current_state != state && self.animations.contains_key(state) {
-            self.current_state = state.to_string();
-            self.frame_counter = 0.0;
-            if let Some(anim) = self.animations.get_mut(state) {
-                anim.current = 0;
-            }
-        }
-    }
-    
-    pub fn update(&mut self) {
-        if let Some(anim) = self.animations.get_mut(&self.current_state) {
-            self.frame_counter += anim.speed;
-            if self.frame_counter >= 1.0 {
-                self.frame_counter = 0.0;
-                anim.current += 1;
-                if anim.current >= anim.frames.len() {
-                    anim.current = if anim.looped { 0 } else { anim.frames.len() - 1 };
-                }
-            }
-        }
-    }
-    
-    pub fn get_current_frame(&self) -> Option<&Sprite> {
-        self.animations.get(&self.current_state).and_then(|anim| anim.frames.get(anim.current))
-    }
-    
-    pub fn load_sprite_sheet(path: &str, frame_width: u32, frame_height: u32, frame_count: usize, scale: u32) -> Vec<Sprite> {
-        let sheet = Sprite::load(path);
-        let mut frames = Vec::with_capacity(frame_count);
-        
-        for i in 0..frame_count {
-            let mut pixels = Vec::with_capacity((frame_width * frame_height) as usize);
-            let start_x = i as u32 * frame_width;
-            
-            for y in 0..frame_height {
-                let src_y = y * sheet.width();
-                for x in 0..frame_width {
-                    let sx = start_x + x;
-                    let color = if sx < sheet.width() && y < sheet.height() {
-                        sheet.pixels[(src_y + sx) as usize]
-                    } else { 0 };
-                    pixels.push(color);
-                }
-            }
-            
-            let mut sprite = Sprite { width: frame_width, height: frame_height, pixels };
-            if scale != 1 {
-                sprite = sprite.scale(scale);
-            }
-            frames.push(sprite);
-        }
-        
-        frames
-    }
-}
\ No newline at end of file
+use crate::assets::{Assets, Handle, SpriteKey};
+use crate::sprite::Sprite;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopDirection {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+impl LoopDirection {
+    // Names as written by Aseprite
+    pub fn from_name(name: &str) -> Option<LoopDirection> {
+        match name {
+            "forward" => Some(LoopDirection::Forward),
+            "reverse" => Some(LoopDirection::Reverse),
+            "pingpong" => Some(LoopDirection::PingPong),
+            "pingpong_reverse" => Some(LoopDirection::PingPongReverse),
+            _ => None,
+        }
+    }
+    
+    // Frame indices for one pass. Ping-pong leaves out the turning frames on the way back
+    // so a looping animation does not show them twice in a row
+    pub fn frame_order(&self, count: usize) -> Vec<usize> {
+        let forward: Vec<usize> = (0..count).collect();
+        let there_and_back = |frames: Vec<usize>| {
+            let back: Vec<usize> = frames.iter().rev().skip(1).take(count.saturating_sub(2)).copied().collect();
+            frames.into_iter().chain(back).collect()
+        };
+        
+        match self {
+            LoopDirection::Forward => forward,
+            LoopDirection::Reverse => forward.into_iter().rev().collect(),
+            LoopDirection::PingPong => there_and_back(forward),
+            LoopDirection::PingPongReverse => there_and_back(forward.into_iter().rev().collect()),
+        }
+    }
+}
+
+// Which way a character faces, y grows downwards like on screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::UpRight,
+        Direction::Right,
+        Direction::DownRight,
+        Direction::Down,
+        Direction::DownLeft,
+        Direction::Left,
+        Direction::UpLeft,
+    ];
+    
+    // Suffix of directional clip names, e.g. "walking_down_left"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::UpRight => "up_right",
+            Direction::Right => "right",
+            Direction::DownRight => "down_right",
+            Direction::Down => "down",
+            Direction::DownLeft => "down_left",
+            Direction::Left => "left",
+            Direction::UpLeft => "up_left",
+        }
+    }
+    
+    pub fn from_name(name: &str) -> Option<Direction> {
+        Self::ALL.into_iter().find(|direction| direction.name() == name)
+    }
+    
+    // Nearest of the 8 (or 4) directions to a movement vector, None while standing still.
+    // 4-way prefers the horizontal clip when moving exactly diagonally
+    pub fn from_vector(dx: f32, dy: f32, eight_way: bool) -> Option<Direction> {
+        if dx == 0.0 && dy == 0.0 {
+            return None;
+        }
+        
+        if eight_way {
+            // Octant 0 is right, counting clockwise because y points down
+            let octant = (dy.atan2(dx) / std::f32::consts::FRAC_PI_4).round() as i32;
+            Some([
+                Direction::Right,
+                Direction::DownRight,
+                Direction::Down,
+                Direction::DownLeft,
+                Direction::Left,
+                Direction::UpLeft,
+                Direction::Up,
+                Direction::UpRight,
+            ][octant.rem_euclid(8) as usize])
+        } else if dx.abs() >= dy.abs() {
+            Some(if dx < 0.0 { Direction::Left } else { Direction::Right })
+        } else {
+            Some(if dy < 0.0 { Direction::Up } else { Direction::Down })
+        }
+    }
+    
+    // Reflected left to right, up and down stay the same
+    pub fn mirrored(&self) -> Direction {
+        match self {
+            Direction::UpRight => Direction::UpLeft,
+            Direction::Right => Direction::Left,
+            Direction::DownRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::DownRight,
+            Direction::Left => Direction::Right,
+            Direction::UpLeft => Direction::UpRight,
+            vertical => *vertical,
+        }
+    }
+    
+    pub fn is_leftward(&self) -> bool {
+        matches!(self, Direction::Left | Direction::UpLeft | Direction::DownLeft)
+    }
+    
+    // Clips to try in order as (direction, draw mirrored): the exact direction, its mirror image,
+    // then for diagonals the vertical and horizontal neighbours of a 4-way set
+    fn fallbacks(&self) -> Vec<(Direction, bool)> {
+        let mut candidates = vec![(*self, false)];
+        if self.mirrored() != *self {
+            candidates.push((self.mirrored(), true));
+        }
+        
+        let (vertical, horizontal) = match self {
+            Direction::UpRight => (Direction::Up, Direction::Right),
+            Direction::DownRight => (Direction::Down, Direction::Right),
+            Direction::DownLeft => (Direction::Down, Direction::Left),
+            Direction::UpLeft => (Direction::Up, Direction::Left),
+            _ => return candidates,
+        };
+        candidates.push((vertical, false));
+        candidates.push((horizontal, false));
+        candidates.push((horizontal.mirrored(), true));
+        candidates
+    }
+}
+
+pub struct Animation {
+    pub frames: Vec<Handle<Sprite>>,
+    // Seconds each frame stays on screen
+    pub durations: Vec<f32>,
+    pub direction: LoopDirection,
+    // Passes to play before the clip finishes, None loops forever
+    pub repeat: Option<u32>,
+    // Named events fired when playback enters a frame, as (index into `frames`, name)
+    pub events: Vec<(usize, String)>,
+    // Indices into `frames` for one pass, built from the direction
+    sequence: Vec<usize>,
+}
+
+pub struct AnimationHandler {
+    animations: HashMap<String, Animation>,
+    // Name the clip was requested by, e.g. "walking"
+    pub current_state: String,
+    facing: Direction,
+    // Clip actually playing for the facing, e.g. "walking_left", and whether it is drawn mirrored
+    clip: String,
+    flipped: bool,
+    // Playback speed multiplier, 2.0 plays twice as fast
+    pub rate: f32,
+    position: usize,
+    elapsed: f32,
+    passes: u32,
+    finished: bool,
+    // Plays the current clip a single time whatever its repeat count
+    once: bool,
+    pending_events: Vec<String>,
+}
+
+impl AnimationHandler {
+    const DEFAULT_FRAME_DURATION: f32 = 0.1;
+    // Keeps zero-length frames from stalling the catch-up loop in update()
+    const MIN_FRAME_DURATION: f32 = 0.001;
+    
+    pub fn new() -> Self {
+        Self {
+            animations: HashMap::with_capacity(16),
+            current_state: String::new(),
+            facing: Direction::Right,
+            clip: String::new(),
+            flipped: false,
+            rate: 1.0,
+            position: 0,
+            elapsed: 0.0,
+            passes: 0,
+            finished: false,
+            once: false,
+            pending_events: Vec::new(),
+        }
+    }
+    
+    pub fn add_animation(
+        &mut self,
+        name: &str,
+        frames: Vec<Handle<Sprite>>,
+        durations: Vec<f32>,
+        direction: LoopDirection,
+        repeat: Option<u32>,
+    ) {
+        let sequence = direction.frame_order(frames.len());
+        self.animations.insert(name.to_string(), Animation {
+            frames, durations, direction, repeat, events: Vec::new(), sequence,
+        });
+    }
+    
+    // Adds the event to the clip and to each of its directional variants.
+    // Returns false when there is no such animation or frame
+    pub fn add_event(&mut self, animation: &str, frame: usize, name: &str) -> bool {
+        let clips = std::iter::once(animation.to_string())
+            .chain(Direction::ALL.iter().map(|direction| format!("{}_{}", animation, direction.name())));
+        
+        let mut added = false;
+        for clip in clips {
+            if let Some(anim) = self.animations.get_mut(&clip)
+                && frame < anim.frames.len() {
+                anim.events.push((frame, name.to_string()));
+                added = true;
+            }
+        }
+        added
+    }
+    
+    pub fn set_state(&mut self, state: &str) {
+        if self.current_state != state && self.resolve(state).is_some() {
+            self.current_state = state.to_string();
+            self.once = false;
+            self.restart();
+        }
+    }
+    
+    // Starts a clip from its first frame even if it is already playing
+    pub fn play(&mut self, name: &str, once: bool) {
+        if self.resolve(name).is_some() {
+            self.current_state = name.to_string();
+            self.once = once;
+            self.restart();
+        }
+    }
+    
+    // Switches to the clip for the new direction without restarting it, so a walk cycle carries on
+    pub fn set_facing(&mut self, facing: Direction) {
+        if self.facing == facing {
+            return;
+        }
+        self.facing = facing;
+        
+        if let Some((clip, flipped)) = self.resolve(&self.current_state) {
+            self.flipped = flipped;
+            if clip != self.clip {
+                let length = self.animations.get(&clip).map(|anim| anim.sequence.len()).unwrap_or(0);
+                self.position = self.position.min(length.saturating_sub(1));
+                self.clip = clip;
+            }
+        }
+    }
+    
+    // True when the clip for the facing is a mirrored copy of the opposite side
+    pub fn is_flipped(&self) -> bool {
+        self.flipped
+    }
+    
+    // Picks "name_direction" for the facing, falling back to mirrored and neighbouring directions
+    // and finally to the plain "name" clip, which is drawn facing right
+    fn resolve(&self, name: &str) -> Option<(String, bool)> {
+        for (direction, mirrored) in self.facing.fallbacks() {
+            let clip = format!("{}_{}", name, direction.name());
+            if self.animations.contains_key(&clip) {
+                return Some((clip, mirrored));
+            }
+        }
+        self.animations.contains_key(name).then(|| (name.to_string(), self.facing.is_leftward()))
+    }
+    
+    pub fn restart(&mut self) {
+        self.position = 0;
+        self.elapsed = 0.0;
+        self.passes = 0;
+        self.finished = false;
+        
+        if let Some((clip, flipped)) = self.resolve(&self.current_state) {
+            self.clip = clip;
+            self.flipped = flipped;
+        }
+        if let Some(anim) = self.animations.get(&self.clip) {
+            Self::emit_events(anim, self.position, &mut self.pending_events);
+        }
+    }
+    
+    fn emit_events(anim: &Animation, position: usize, pending: &mut Vec<String>) {
+        if let Some(&frame) = anim.sequence.get(position) {
+            pending.extend(anim.events.iter().filter(|(at, _)| *at == frame).map(|(_, name)| name.clone()));
+        }
+    }
+    
+    // Events crossed since the last call, in the order playback reached them
+    pub fn drain_events(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.pending_events)
+    }
+    
+    // Advances by `delta` seconds scaled by `rate`, skipping as many frames as that covers
+    pub fn update(&mut self, delta: f32) {
+        let anim = match self.animations.get(&self.clip) {
+            Some(anim) if !anim.sequence.is_empty() => anim,
+            _ => return,
+        };
+        if self.finished {
+            return;
+        }
+        
+        self.elapsed += delta * self.rate.max(0.0);
+        loop {
+            let frame = anim.sequence[self.position];
+            let duration = anim.durations.get(frame).copied()
+                .unwrap_or(Self::DEFAULT_FRAME_DURATION)
+                .max(Self::MIN_FRAME_DURATION);
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+            
+            if self.position + 1 < anim.sequence.len() {
+                self.position += 1;
+                Self::emit_events(anim, self.position, &mut self.pending_events);
+                continue;
+            }
+            
+            self.passes += 1;
+            let repeat = if self.once { Some(1) } else { anim.repeat };
+            if repeat.is_some_and(|repeat| self.passes >= repeat) {
+                self.finished = true;
+                self.elapsed = 0.0;
+                // A bounce ends back where it started, other clips hold their last frame
+                if matches!(anim.direction, LoopDirection::PingPong | LoopDirection::PingPongReverse) {
+                    self.position = 0;
+                }
+                break;
+            }
+            self.position = 0;
+            Self::emit_events(anim, self.position, &mut self.pending_events);
+        }
+    }
+    
+    // True once a clip with a repeat count has played all its passes, looping clips never finish
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    
+    // How far through the current pass playback is, from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        if self.finished {
+            return 1.0;
+        }
+        let anim = match self.animations.get(&self.clip) {
+            Some(anim) => anim,
+            None => return 0.0,
+        };
+        
+        let duration_of = |frame: usize| anim.durations.get(frame).copied()
+            .unwrap_or(Self::DEFAULT_FRAME_DURATION)
+            .max(Self::MIN_FRAME_DURATION);
+        let total: f32 = anim.sequence.iter().map(|&frame| duration_of(frame)).sum();
+        let played: f32 = anim.sequence[..self.position].iter().map(|&frame| duration_of(frame)).sum();
+        if total <= 0.0 { 0.0 } else { ((played + self.elapsed) / total).min(1.0) }
+    }
+    
+    pub fn get_current_frame(&self) -> Option<&Handle<Sprite>> {
+        self.animations.get(&self.clip)
+            .and_then(|anim| anim.sequence.get(self.position).and_then(|&frame| anim.frames.get(frame)))
+    }
+    
+    // Single row of equal frames, left to right
+    pub fn load_sprite_sheet(assets: &mut Assets, sheet: &SpriteKey, frame_width: u32, frame_height: u32, frame_count: usize) -> Vec<Handle<Sprite>> {
+        Self::load_sprite_grid(assets, sheet, &GridLayout::new(frame_width, frame_height), Some(frame_count))
+    }
+    
+    // Cells are read row by row. Without a frame count every whole cell on the sheet is used
+    pub fn load_sprite_grid(assets: &mut Assets, sheet: &SpriteKey, layout: &GridLayout, frame_count: Option<usize>) -> Vec<Handle<Sprite>> {
+        let (sheet_width, sheet_height) = match sheet.region {
+            Some((_, _, width, height)) => (width, height),
+            None => {
+                let source = assets.sprite(SpriteKey::new(&sheet.path));
+                let source = source.get();
+                (source.width(), source.height())
+            }
+        };
+        
+        let mut rects = layout.frame_rects(sheet_width, sheet_height);
+        if let Some(count) = frame_count {
+            if count > rects.len() {
+                eprintln!("{} has {} frames of {}x{}, {} requested", sheet.path, rects.len(), layout.frame_width, layout.frame_height, count);
+            }
+            rects.truncate(count);
+        }
+        Self::load_sprite_rects(assets, sheet, &rects)
+    }
+    
+    // Explicit (x, y, width, height) rectangles in unscaled sheet pixels, for sheets that are not a grid
+    pub fn load_sprite_rects(assets: &mut Assets, sheet: &SpriteKey, rects: &[(u32, u32, u32, u32)]) -> Vec<Handle<Sprite>> {
+        rects.iter()
+            .map(|&(x, y, width, height)| assets.sprite(sheet.clone().region(x, y, width, height)))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GridLayout {
+    pub frame_width: u32,
+    pub frame_height: u32,
+    // Border around the whole grid and gap between neighbouring cells, in unscaled pixels
+    pub margin: u32,
+    pub spacing: u32,
+}
+
+impl GridLayout {
+    pub fn new(frame_width: u32, frame_height: u32) -> Self {
+        Self { frame_width, frame_height, margin: 0, spacing: 0 }
+    }
+    
+    pub fn with_margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+    
+    pub fn with_spacing(mut self, spacing: u32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+    
+    pub fn frame_rects(&self, sheet_width: u32, sheet_height: u32) -> Vec<(u32, u32, u32, u32)> {
+        if self.frame_width == 0 || self.frame_height == 0 {
+            return Vec::new();
+        }
+        
+        let cells = |sheet: u32, frame: u32| {
+            (sheet.saturating_sub(2 * self.margin) + self.spacing) / (frame + self.spacing)
+        };
+        let columns = cells(sheet_width, self.frame_width);
+        let rows = cells(sheet_height, self.frame_height);
+        
+        let mut rects = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                rects.push((
+                    self.margin + column * (self.frame_width + self.spacing),
+                    self.margin + row * (self.frame_height + self.spacing),
+                    self.frame_width,
+                    self.frame_height,
+                ));
+            }
+        }
+        rects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnimationHandler, Direction, GridLayout, LoopDirection};
+    use crate::assets::Handle;
+    use crate::sprite::Sprite;
+    
+    // One pixel frames whose color is their index, so tests can tell which one is showing
+    fn handler_with(name: &str, durations: Vec<f32>, direction: LoopDirection, repeat: Option<u32>) -> AnimationHandler {
+        let frames = (0..durations.len() as u32).map(|i| Handle::new(Sprite::new(1, 1, vec![i]))).collect();
+        let mut animations = AnimationHandler::new();
+        animations.add_animation(name, frames, durations, direction, repeat);
+        animations
+    }
+    
+    fn shown(animations: &AnimationHandler) -> u32 {
+        animations.get_current_frame().expect("a frame").get().pixel(0, 0)
+    }
+    
+    #[test]
+    fn frame_order_follows_the_direction() {
+        assert_eq!(LoopDirection::Forward.frame_order(3), vec![0, 1, 2]);
+        assert_eq!(LoopDirection::Reverse.frame_order(3), vec![2, 1, 0]);
+        assert_eq!(LoopDirection::PingPong.frame_order(4), vec![0, 1, 2, 3, 2, 1]);
+        assert_eq!(LoopDirection::PingPongReverse.frame_order(3), vec![2, 1, 0, 1]);
+        assert_eq!(LoopDirection::PingPong.frame_order(1), vec![0]);
+    }
+    
+    #[test]
+    fn update_uses_per_frame_durations_and_catches_up() {
+        let mut animations = handler_with("idle", vec![0.1, 0.3, 0.1], LoopDirection::Forward, None);
+        animations.play("idle", false);
+        
+        animations.update(0.05);
+        assert_eq!(shown(&animations), 0);
+        animations.update(0.1);
+        assert_eq!(shown(&animations), 1);
+        // A long tick skips every frame it covers and wraps around
+        animations.update(0.4);
+        assert_eq!(shown(&animations), 0);
+        assert!(!animations.is_finished());
+        
+        animations.rate = 2.0;
+        animations.update(0.05);
+        assert_eq!(shown(&animations), 1);
+    }
+    
+    #[test]
+    fn repeat_counts_finish_the_clip() {
+        let mut animations = handler_with("death", vec![0.1; 3], LoopDirection::Forward, Some(2));
+        animations.play("death", false);
+        animations.update(0.55);
+        assert!(!animations.is_finished());
+        
+        animations.update(0.1);
+        assert!(animations.is_finished());
+        assert_eq!(shown(&animations), 2);
+        assert_eq!(animations.progress(), 1.0);
+        
+        // A bounce settles back on its first frame
+        let mut animations = handler_with("bob", vec![0.1; 3], LoopDirection::PingPong, None);
+        animations.play("bob", true);
+        animations.update(0.45);
+        assert!(animations.is_finished());
+        assert_eq!(shown(&animations), 0);
+    }
+    
+    #[test]
+    fn facing_picks_directional_clips_and_mirrors() {
+        let mut animations = handler_with("walking", vec![0.1; 2], LoopDirection::Forward, None);
+        animations.add_animation("walking_up", vec![Handle::new(Sprite::new(1, 1, vec![7]))], vec![0.1], LoopDirection::Forward, None);
+        animations.play("walking", false);
+        animations.update(0.15);
+        
+        animations.set_facing(Direction::Left);
+        assert!(animations.is_flipped());
+        // Turning keeps the walk cycle going instead of restarting it
+        assert_eq!(shown(&animations), 1);
+        
+        animations.set_facing(Direction::Up);
+        assert!(!animations.is_flipped());
+        assert_eq!(shown(&animations), 7);
+    }
+    
+    #[test]
+    fn events_fire_when_playback_enters_their_frame() {
+        let mut animations = handler_with("walking", vec![0.1; 4], LoopDirection::Forward, None);
+        animations.add_animation("walking_up", vec![Handle::new(Sprite::new(1, 1, vec![0])); 4], vec![0.1; 4], LoopDirection::Forward, None);
+        assert!(animations.add_event("walking", 0, "start"));
+        assert!(animations.add_event("walking", 1, "footstep"));
+        assert!(animations.add_event("walking", 3, "footstep_right"));
+        assert!(!animations.add_event("walking", 4, "past_the_end"));
+        assert!(!animations.add_event("running", 0, "missing"));
+        
+        animations.play("walking", false);
+        assert_eq!(animations.drain_events(), vec!["start"]);
+        
+        // Frames skipped by a long tick still fire, in order, and wrapping fires frame 0 again
+        animations.update(0.45);
+        assert_eq!(animations.drain_events(), vec!["footstep", "footstep_right", "start"]);
+        assert!(animations.drain_events().is_empty());
+        
+        // Directional variants get the events too
+        animations.set_facing(Direction::Up);
+        animations.update(0.1);
+        assert_eq!(animations.drain_events(), vec!["footstep"]);
+    }
+    
+    #[test]
+    fn frame_rects_reads_rows_with_margin_and_spacing() {
+        let layout = GridLayout::new(100, 100).with_margin(2).with_spacing(2);
+        let rects = layout.frame_rects(920, 308);
+        
+        assert_eq!(rects.len(), 27);
+        assert_eq!(rects[0], (2, 2, 100, 100));
+        assert_eq!(rects[1], (104, 2, 100, 100));
+        assert_eq!(rects[8], (818, 2, 100, 100));
+        assert_eq!(rects[9], (2, 104, 100, 100));
+        assert_eq!(rects[26], (818, 206, 100, 100));
+    }
+    
+    #[test]
+    fn frame_rects_skips_partial_cells() {
+        // One pixel short of a second column and row
+        let rects = GridLayout::new(10, 10).with_margin(1).with_spacing(1).frame_rects(22, 22);
+        assert_eq!(rects, vec![(1, 1, 10, 10)]);
+        
+        assert!(GridLayout::new(0, 10).frame_rects(100, 100).is_empty());
+        assert!(GridLayout::new(10, 10).with_margin(60).frame_rects(100, 100).is_empty());
+    }
+}
@@ -0,0 +1,430 @@
+use crate::animation_manager::AnimationHandler;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+}
+
+// A test on one parameter. Booleans are stored as 1.0 and 0.0
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub parameter: String,
+    pub comparison: Comparison,
+    pub value: f32,
+}
+
+impl Condition {
+    // "speed > 0", "health <= 0", "is_hurt" (is true) or "!is_dead" (is false)
+    pub fn parse(text: &str) -> Option<Condition> {
+        let text = text.trim();
+        // Two-character operators first so ">=" is not read as ">"
+        let operators = [
+            (">=", Comparison::GreaterEqual),
+            ("<=", Comparison::LessEqual),
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+        ];
+        
+        for (symbol, comparison) in operators {
+            if let Some((parameter, value)) = text.split_once(symbol) {
+                let value = match value.trim() {
+                    "true" => 1.0,
+                    "false" => 0.0,
+                    number => number.parse().ok()?,
+                };
+                return Some(Condition { parameter: parameter.trim().to_string(), comparison, value });
+            }
+        }
+        
+        match text.strip_prefix('!') {
+            Some(parameter) => Some(Condition { parameter: parameter.trim().to_string(), comparison: Comparison::Equal, value: 0.0 }),
+            None if !text.is_empty() => Some(Condition { parameter: text.to_string(), comparison: Comparison::NotEqual, value: 0.0 }),
+            None => None,
+        }
+    }
+    
+    // Parameters that were never set read as 0
+    pub fn holds(&self, parameters: &HashMap<String, f32>) -> bool {
+        let actual = parameters.get(&self.parameter).copied().unwrap_or(0.0);
+        match self.comparison {
+            Comparison::Greater => actual > self.value,
+            Comparison::GreaterEqual => actual >= self.value,
+            Comparison::Less => actual < self.value,
+            Comparison::LessEqual => actual <= self.value,
+            Comparison::Equal => actual == self.value,
+            Comparison::NotEqual => actual != self.value,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationState {
+    pub animation: String,
+    // Playback rate while in this state
+    pub speed: f32,
+    // False plays the clip once and holds the last frame
+    pub looped: bool,
+    // Seconds before the state ends on its own, e.g. a short hurt flash
+    pub duration: Option<f32>,
+    // Where the state goes once its clip (or duration) is over. "previous" goes back
+    // to the state it interrupted, which makes hurt and attack "play once then return"
+    pub return_to: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Transition {
+    // None can fire from any state
+    pub from: Option<String>,
+    pub to: String,
+    // All of them have to hold
+    pub conditions: Vec<Condition>,
+    // Fraction of the current clip that has to have played first, 1.0 waits for its end
+    pub exit_time: Option<f32>,
+    // Higher is checked first
+    pub priority: i32,
+}
+
+// Picks the animation from parameters set by gameplay instead of gameplay naming clips directly
+pub struct AnimationStateMachine {
+    states: HashMap<String, AnimationState>,
+    transitions: Vec<Transition>,
+    parameters: HashMap<String, f32>,
+    // Parameters that reset once a transition using them fires
+    triggers: Vec<String>,
+    current: String,
+    // Last state without return_to, where "previous" goes back to
+    resume: String,
+    time_in_state: f32,
+}
+
+impl AnimationStateMachine {
+    pub const RETURN_TO_PREVIOUS: &str = "previous";
+    
+    pub fn new(initial: &str) -> Self {
+        Self {
+            states: HashMap::new(),
+            transitions: Vec::new(),
+            parameters: HashMap::new(),
+            triggers: Vec::new(),
+            current: initial.to_string(),
+            resume: initial.to_string(),
+            time_in_state: 0.0,
+        }
+    }
+    
+    // Reads a "states" block as used in enemies.json:
+    //     "walking": {"animation": "walking", "animation_speed": 1.2,
+    //                 "transitions": [{"to": "idle", "when": "speed <= 0"}]},
+    //     "hurt": {"animation": "hurt", "duration": 0.5, "return_to": "previous", "enter_when": "is_hurt"}
+    // "when"/"enter_when" take one condition or a list of them, "enter_when" fires from any state
+    pub fn from_json(states: &Value, initial: &str) -> Option<Self> {
+        let states = states.as_object()?;
+        let mut machine = Self::new(initial);
+        
+        for (name, info) in states {
+            machine.add_state(name, AnimationState {
+                animation: info["animation"].as_str().unwrap_or(name).to_string(),
+                speed: info["animation_speed"].as_f64().unwrap_or(1.0) as f32,
+                looped: info["looped"].as_bool().unwrap_or(true),
+                duration: info["duration"].as_f64().map(|d| d as f32),
+                return_to: info["return_to"].as_str().map(|s| s.to_string()),
+            });
+            
+            if !info["enter_when"].is_null() {
+                machine.add_transition(Transition {
+                    from: None,
+                    to: name.clone(),
+                    conditions: parse_conditions(&info["enter_when"], name),
+                    exit_time: None,
+                    priority: info["priority"].as_i64().unwrap_or(0) as i32,
+                });
+            }
+            
+            for transition in info["transitions"].as_array().into_iter().flatten() {
+                let to = match transition["to"].as_str() {
+                    Some(to) => to.to_string(),
+                    None => {
+                        eprintln!("Transition out of state {} has no \"to\"", name);
+                        continue;
+                    }
+                };
+                machine.add_transition(Transition {
+                    from: Some(name.clone()),
+                    to,
+                    conditions: parse_conditions(&transition["when"], name),
+                    exit_time: transition["exit_time"].as_f64().map(|t| t as f32),
+                    priority: transition["priority"].as_i64().unwrap_or(0) as i32,
+                });
+            }
+        }
+        
+        for transition in &machine.transitions {
+            if !machine.states.contains_key(&transition.to) {
+                eprintln!("Transition points at unknown state {}", transition.to);
+            }
+        }
+        if !machine.states.contains_key(initial) {
+            eprintln!("Initial animation state {} is not defined", initial);
+        }
+        Some(machine)
+    }
+    
+    pub fn add_state(&mut self, name: &str, state: AnimationState) {
+        self.states.insert(name.to_string(), state);
+    }
+    
+    pub fn add_transition(&mut self, transition: Transition) {
+        // Stable, so equal priorities keep the order they were added in
+        let index = self.transitions.partition_point(|t| t.priority >= transition.priority);
+        self.transitions.insert(index, transition);
+    }
+    
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.parameters.insert(name.to_string(), value);
+    }
+    
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.set_float(name, if value { 1.0 } else { 0.0 });
+    }
+    
+    // A bool that clears itself after causing a transition, for one-off things like a hit
+    pub fn set_trigger(&mut self, name: &str) {
+        self.set_bool(name, true);
+        if !self.triggers.iter().any(|t| t == name) {
+            self.triggers.push(name.to_string());
+        }
+    }
+    
+    pub fn current_state(&self) -> &str {
+        &self.current
+    }
+    
+    // Plays the current state's clip, call once after the animations are added
+    pub fn start(&mut self, animations: &mut AnimationHandler) {
+        let state = self.current.clone();
+        self.enter(&state, animations);
+    }
+    
+    // Takes at most one transition, then advances the clip
+    pub fn update(&mut self, animations: &mut AnimationHandler, delta: f32) {
+        self.time_in_state += delta;
+        
+        if let Some(index) = self.next_transition(animations) {
+            let to = self.transitions[index].to.clone();
+            self.consume_triggers(index);
+            // An any-state entry into the current state only holds it there
+            if to != self.current {
+                self.enter(&to, animations);
+            }
+        } else if let Some(target) = self.return_target(animations) {
+            self.enter(&target, animations);
+        }
+        
+        animations.update(delta);
+    }
+    
+    // The first transition that can fire, in priority order. An any-state entry into the
+    // current state still counts so a dead enemy is not pulled into hurt by a lower rule
+    fn next_transition(&self, animations: &AnimationHandler) -> Option<usize> {
+        for (index, transition) in self.transitions.iter().enumerate() {
+            if transition.from.as_ref().is_some_and(|from| *from != self.current) {
+                continue;
+            }
+            if !transition.conditions.iter().all(|c| c.holds(&self.parameters)) {
+                continue;
+            }
+            if transition.exit_time.is_some_and(|exit| animations.progress() < exit) {
+                continue;
+            }
+            return Some(index);
+        }
+        None
+    }
+    
+    fn consume_triggers(&mut self, index: usize) {
+        for condition in &self.transitions[index].conditions {
+            if self.triggers.contains(&condition.parameter) {
+                self.parameters.insert(condition.parameter.clone(), 0.0);
+            }
+        }
+    }
+    
+    // Where a returning state goes once its clip or duration is over
+    fn return_target(&self, animations: &AnimationHandler) -> Option<String> {
+        let state = self.states.get(&self.current)?;
+        let return_to = state.return_to.as_ref()?;
+        let over = match state.duration {
+            Some(duration) => self.time_in_state >= duration,
+            None => animations.is_finished(),
+        };
+        if !over {
+            return None;
+        }
+        
+        if return_to == Self::RETURN_TO_PREVIOUS {
+            Some(self.resume.clone())
+        } else {
+            Some(return_to.clone())
+        }
+    }
+    
+    fn enter(&mut self, name: &str, animations: &mut AnimationHandler) {
+        let (animation, speed, once) = match self.states.get(name) {
+            // Returning states without a duration end with their clip, so it must not loop
+            Some(state) => (
+                state.animation.clone(),
+                state.speed,
+                !state.looped || (state.return_to.is_some() && state.duration.is_none()),
+            ),
+            None => return,
+        };
+        
+        if self.states.get(&self.current).is_some_and(|current| current.return_to.is_none()) {
+            self.resume = self.current.clone();
+        }
+        
+        animations.rate = speed;
+        animations.play(&animation, once);
+        
+        self.current = name.to_string();
+        self.time_in_state = 0.0;
+    }
+}
+
+fn parse_conditions(value: &Value, state: &str) -> Vec<Condition> {
+    let texts: Vec<&str> = match value {
+        Value::String(text) => vec![text.as_str()],
+        Value::Array(list) => list.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    
+    texts.into_iter()
+        .filter_map(|text| {
+            let condition = Condition::parse(text);
+            if condition.is_none() {
+                eprintln!("Invalid condition \"{}\" in animation state {}", text, state);
+            }
+            condition
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnimationStateMachine, Comparison, Condition};
+    use crate::animation_manager::{AnimationHandler, LoopDirection};
+    use crate::assets::Handle;
+    use crate::sprite::Sprite;
+    use serde_json::json;
+    
+    // Two frames of 0.1 seconds for every clip, so one pass takes 0.2
+    fn animations_for(names: &[&str]) -> AnimationHandler {
+        let mut animations = AnimationHandler::new();
+        for name in names {
+            let frames = vec![Handle::new(Sprite::new(1, 1, vec![0])); 2];
+            animations.add_animation(name, frames, vec![0.1; 2], LoopDirection::Forward, None);
+        }
+        animations
+    }
+    
+    fn player_states() -> AnimationStateMachine {
+        let states = json!({
+            "idle": {"transitions": [{"to": "walking", "when": "speed > 0"}]},
+            "walking": {"transitions": [{"to": "idle", "when": "speed <= 0"}]},
+            "attack": {"return_to": "previous", "enter_when": "attack", "priority": 1},
+            "hurt": {"duration": 0.25, "return_to": "previous", "enter_when": "is_hurt", "priority": 2},
+            "death": {"looped": false, "enter_when": "is_dead", "priority": 3}
+        });
+        AnimationStateMachine::from_json(&states, "idle").expect("states")
+    }
+    
+    #[test]
+    fn conditions_parse_operators_and_flags() {
+        let condition = Condition::parse(" health >= 10 ").expect("comparison");
+        assert_eq!((condition.parameter.as_str(), condition.comparison, condition.value), ("health", Comparison::GreaterEqual, 10.0));
+        
+        let condition = Condition::parse("is_hurt == true").expect("bool literal");
+        assert_eq!((condition.comparison, condition.value), (Comparison::Equal, 1.0));
+        
+        let condition = Condition::parse("is_dead").expect("bare flag");
+        assert_eq!((condition.comparison, condition.value), (Comparison::NotEqual, 0.0));
+        let condition = Condition::parse("!is_dead").expect("negated flag");
+        assert_eq!((condition.parameter.as_str(), condition.comparison), ("is_dead", Comparison::Equal));
+        
+        assert!(Condition::parse("speed > fast").is_none());
+        assert!(Condition::parse("  ").is_none());
+    }
+    
+    #[test]
+    fn transitions_follow_parameters() {
+        let mut animations = animations_for(&["idle", "walking", "attack", "hurt", "death"]);
+        let mut states = player_states();
+        states.start(&mut animations);
+        assert_eq!(states.current_state(), "idle");
+        
+        states.set_float("speed", 3.0);
+        states.update(&mut animations, 0.016);
+        assert_eq!(states.current_state(), "walking");
+        
+        states.set_float("speed", 0.0);
+        states.update(&mut animations, 0.016);
+        assert_eq!(states.current_state(), "idle");
+    }
+    
+    #[test]
+    fn return_to_previous_resumes_the_interrupted_state() {
+        let mut animations = animations_for(&["idle", "walking", "attack", "hurt", "death"]);
+        let mut states = player_states();
+        states.start(&mut animations);
+        states.set_float("speed", 3.0);
+        states.update(&mut animations, 0.016);
+        
+        // Triggers clear once used, so the attack plays a single time
+        states.set_trigger("attack");
+        states.update(&mut animations, 0.016);
+        assert_eq!(states.current_state(), "attack");
+        states.update(&mut animations, 0.1);
+        assert_eq!(states.current_state(), "attack");
+        states.update(&mut animations, 0.1);
+        states.update(&mut animations, 0.016);
+        assert_eq!(states.current_state(), "walking");
+        
+        // Hurt ends on its duration rather than its clip
+        states.set_bool("is_hurt", true);
+        states.update(&mut animations, 0.016);
+        states.set_bool("is_hurt", false);
+        assert_eq!(states.current_state(), "hurt");
+        states.update(&mut animations, 0.2);
+        assert_eq!(states.current_state(), "hurt");
+        states.update(&mut animations, 0.1);
+        assert_eq!(states.current_state(), "walking");
+    }
+    
+    #[test]
+    fn higher_priorities_win_and_hold_their_state() {
+        let mut animations = animations_for(&["idle", "walking", "attack", "hurt", "death"]);
+        let mut states = player_states();
+        states.start(&mut animations);
+        
+        states.set_bool("is_hurt", true);
+        states.set_bool("is_dead", true);
+        states.update(&mut animations, 0.016);
+        assert_eq!(states.current_state(), "death");
+        
+        // Death outranks hurt, so it is not pulled back out
+        for _ in 0..30 {
+            states.update(&mut animations, 0.1);
+        }
+        assert_eq!(states.current_state(), "death");
+        assert!(animations.is_finished());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::animation_manager::{AnimationHandler, LoopDirection};
+use crate::assets::{Assets, Pivot, SpriteKey};
+use crate::sprite::named_pivot_point;
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(Clone, Debug)]
+pub struct AsepriteFrame {
+    // Where the frame sits in the sheet image
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // Trimmed exports drop transparent borders, this is where the kept part sat on the canvas
+    pub offset_x: u32,
+    pub offset_y: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+    // Seconds
+    pub duration: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    pub direction: LoopDirection,
+    // Number of passes before stopping, None loops forever
+    pub repeat: Option<u32>,
+    // Frame events from the tag's user data, frames counted from the start of the tag
+    pub events: Vec<(usize, String)>,
+}
+
+// Aseprite's "Export Sprite Sheet" JSON, either the array or the hash frame layout
+pub struct AsepriteSheet {
+    pub image: String,
+    pub frames: Vec<AsepriteFrame>,
+    pub tags: Vec<AsepriteTag>,
+}
+
+impl AsepriteSheet {
+    pub fn load(json_path: &str) -> Option<Self> {
+        let data: Value = match std::fs::read_to_string(json_path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Failed to parse JSON {}: {}", json_path, e);
+                    return None;
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to read JSON file {}: {}", json_path, e);
+                return None;
+            }
+        };
+        
+        let image = match data["meta"]["image"].as_str() {
+            Some(image) => image,
+            None => {
+                eprintln!("Aseprite sheet {} has no meta.image", json_path);
+                return None;
+            }
+        };
+        // The image path is relative to the JSON file
+        let base_dir = Path::new(json_path).parent().unwrap_or(Path::new(""));
+        let image = base_dir.join(image).to_string_lossy().replace('\\', "/");
+        
+        let entries: Vec<&Value> = match &data["frames"] {
+            Value::Array(frames) => frames.iter().collect(),
+            Value::Object(frames) => {
+                // serde_json sorts object keys, put "name 10" after "name 9" again
+                let mut named: Vec<(&String, &Value)> = frames.iter().collect();
+                named.sort_by_key(|(name, _)| (frame_number(name), (*name).clone()));
+                named.into_iter().map(|(_, frame)| frame).collect()
+            }
+            _ => Vec::new(),
+        };
+        
+        let field = |value: &Value, key: &str| value[key].as_u64().unwrap_or(0) as u32;
+        let frames: Vec<AsepriteFrame> = entries.iter().map(|entry| {
+            let rect = &entry["frame"];
+            let source = &entry["spriteSourceSize"];
+            let source_size = &entry["sourceSize"];
+            AsepriteFrame {
+                x: field(rect, "x"),
+                y: field(rect, "y"),
+                width: field(rect, "w"),
+                height: field(rect, "h"),
+                offset_x: field(source, "x"),
+                offset_y: field(source, "y"),
+                source_width: source_size["w"].as_u64().map(|w| w as u32).unwrap_or(field(rect, "w")),
+                source_height: source_size["h"].as_u64().map(|h| h as u32).unwrap_or(field(rect, "h")),
+                duration: entry["duration"].as_f64().unwrap_or(100.0) as f32 / 1000.0,
+            }
+        }).collect();
+        
+        if entries.iter().any(|entry| entry["rotated"].as_bool() == Some(true)) {
+            eprintln!("Aseprite sheet {} has rotated frames, export without rotation", json_path);
+        }
+        
+        let tags = data["meta"]["frameTags"].as_array().map(|tags| {
+            tags.iter().filter_map(|tag| {
+                let name = tag["name"].as_str()?.to_string();
+                let from = tag["from"].as_u64()? as usize;
+                let to = tag["to"].as_u64()? as usize;
+                if from > to || to >= frames.len() {
+                    eprintln!("Tag {} in {} points past the last frame", name, json_path);
+                    return None;
+                }
+                
+                Some(AsepriteTag {
+                    name,
+                    from,
+                    to,
+                    direction: tag["direction"].as_str().and_then(LoopDirection::from_name).unwrap_or(LoopDirection::Forward),
+                    // Aseprite writes the repeat count as a string
+                    repeat: tag["repeat"].as_str().and_then(|r| r.parse().ok())
+                        .or_else(|| tag["repeat"].as_u64().map(|r| r as u32))
+                        .filter(|&r| r > 0),
+                    events: tag["data"].as_str().map(parse_tag_events).unwrap_or_default(),
+                })
+            }).collect()
+        }).unwrap_or_default();
+        
+        Some(AsepriteSheet { image, frames, tags })
+    }
+    
+    pub fn tag(&self, name: &str) -> Option<&AsepriteTag> {
+        self.tags.iter().find(|tag| tag.name == name)
+    }
+    
+    // Sprite key for one frame. The pivot is given on the untrimmed canvas (after scaling)
+    // and moved by the trim offset, so trimmed and untrimmed exports line up the same
+    pub fn frame_key(&self, index: usize, scale: u32, pivot: Option<&Pivot>) -> SpriteKey {
+        let frame = &self.frames[index];
+        let scale = scale.max(1);
+        let (canvas_width, canvas_height) = (frame.source_width * scale, frame.source_height * scale);
+        
+        let (pivot_x, pivot_y) = match pivot {
+            Some(Pivot::At(x, y)) => (*x, *y),
+            Some(Pivot::Named(name)) => named_pivot_point(name, canvas_width, canvas_height)
+                .unwrap_or((canvas_width as i32 / 2, canvas_height as i32 / 2)),
+            None => (canvas_width as i32 / 2, canvas_height as i32 / 2),
+        };
+        
+        SpriteKey::new(&self.image)
+            .region(frame.x, frame.y, frame.width, frame.height)
+            .scale(scale)
+            .pivot_at(
+                pivot_x - (frame.offset_x * scale) as i32,
+                pivot_y - (frame.offset_y * scale) as i32,
+            )
+    }
+    
+    // Adds one animation per tag with its frames, durations, direction and repeat count
+    pub fn populate(&self, assets: &mut Assets, animations: &mut AnimationHandler, scale: u32, pivot: Option<&Pivot>, trim: bool) {
+        for tag in &self.tags {
+            let mut frames = Vec::with_capacity(tag.to - tag.from + 1);
+            let mut durations = Vec::with_capacity(tag.to - tag.from + 1);
+            
+            for index in tag.from..=tag.to {
+                let mut key = self.frame_key(index, scale, pivot);
+                if trim {
+                    key = key.trimmed();
+                }
+                frames.push(assets.sprite(key));
+                durations.push(self.frames[index].duration);
+            }
+            
+            animations.add_animation(&tag.name, frames, durations, tag.direction, tag.repeat);
+            for (frame, event) in &tag.events {
+                if !animations.add_event(&tag.name, *frame, event) {
+                    eprintln!("Event {} on tag {} is past its last frame", event, tag.name);
+                }
+            }
+        }
+    }
+}
+
+// Tag user data lists events as "name@frame" separated by commas, e.g. "footstep@1, footstep@5"
+fn parse_tag_events(data: &str) -> Vec<(usize, String)> {
+    data.split(',')
+        .filter_map(|entry| {
+            let (name, frame) = entry.trim().rsplit_once('@')?;
+            Some((frame.trim().parse().ok()?, name.trim().to_string()))
+        })
+        .collect()
+}
+
+// Trailing number of an Aseprite frame name like "player 12.aseprite"
+fn frame_number(name: &str) -> Option<u32> {
+    let stem = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(name);
+    let digits: String = stem.chars().rev().take_while(|c| c.is_ascii_digit()).collect();
+    digits.chars().rev().collect::<String>().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_number, parse_tag_events, AsepriteFrame, AsepriteSheet};
+    use crate::animation_manager::LoopDirection;
+    use crate::assets::Pivot;
+    
+    #[test]
+    fn loads_the_player_export() {
+        let sheet = AsepriteSheet::load("assets/sprites/player/player.json").expect("player export");
+        assert_eq!(sheet.image, "assets/sprites/player/player.png");
+        assert_eq!(sheet.frames.len(), 25);
+        assert_eq!((sheet.frames[6].x, sheet.frames[6].width), (600, 100));
+        assert!((sheet.frames[0].duration - 0.12).abs() < 1e-6);
+        
+        let walking = sheet.tag("walking").expect("walking tag");
+        assert_eq!((walking.from, walking.to), (6, 13));
+        assert_eq!(walking.direction, LoopDirection::Forward);
+        assert_eq!(walking.repeat, None);
+        assert_eq!(walking.events, vec![(1, "footstep".to_string()), (5, "footstep".to_string())]);
+        // Aseprite writes repeat as a string
+        assert_eq!(sheet.tag("death").and_then(|tag| tag.repeat), Some(1));
+        assert!(sheet.tag("missing").is_none());
+    }
+    
+    #[test]
+    fn tag_events_skip_malformed_entries() {
+        assert_eq!(
+            parse_tag_events("footstep@1, hit @ 3,no_frame,bad@x"),
+            vec![(1, "footstep".to_string()), (3, "hit".to_string())]
+        );
+        assert!(parse_tag_events("").is_empty());
+    }
+    
+    #[test]
+    fn frame_numbers_sort_hash_exports() {
+        assert_eq!(frame_number("player 12.aseprite"), Some(12));
+        assert_eq!(frame_number("walk_3"), Some(3));
+        assert_eq!(frame_number("idle.aseprite"), None);
+    }
+    
+    #[test]
+    fn trimmed_frames_keep_the_canvas_pivot() {
+        // A 40x60 frame cut from a 100x100 canvas, 30 in from the left and 40 down
+        let sheet = AsepriteSheet {
+            image: "sheet.png".to_string(),
+            frames: vec![AsepriteFrame {
+                x: 10,
+                y: 20,
+                width: 40,
+                height: 60,
+                offset_x: 30,
+                offset_y: 40,
+                source_width: 100,
+                source_height: 100,
+                duration: 0.1,
+            }],
+            tags: Vec::new(),
+        };
+        
+        let key = sheet.frame_key(0, 2, Some(&Pivot::At(100, 200)));
+        assert_eq!(key.region, Some((10, 20, 40, 60)));
+        assert_eq!(key.scale, 2);
+        assert_eq!(key.pivot, Some(Pivot::At(40, 120)));
+        // No pivot falls back to the center of the untrimmed canvas
+        assert_eq!(sheet.frame_key(0, 1, None).pivot, Some(Pivot::At(20, 10)));
+    }
+}
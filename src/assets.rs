@@ -0,0 +1,365 @@
+use crate::helper_methods::parse_hex_color;
+use crate::sprite::Sprite;
+use serde_json::Value;
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+// Shared reference to a loaded asset, cloning one only bumps a reference count
+pub struct Handle<T>(Rc<RefCell<T>>);
+
+impl<T> Handle<T> {
+    // Wraps a value that is not owned by the store, e.g. a sprite generated at runtime
+    pub fn new(value: T) -> Self {
+        Handle(Rc::new(RefCell::new(value)))
+    }
+    
+    pub fn get(&self) -> Ref<'_, T> {
+        self.0.borrow()
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle(Rc::clone(&self.0))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Pivot {
+    // One of the presets understood by Sprite::with_named_pivot
+    Named(String),
+    // Exact point in pixels of the final, scaled sprite
+    At(i32, i32),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PaletteSwap {
+    // (from, to) colors, compared on RGB only so the source alpha is kept
+    Map(Vec<(u32, u32)>),
+    // Lookup image whose first row holds the original colors and second row their replacements
+    Image(String),
+}
+
+impl PaletteSwap {
+    // Either {"#from": "#to", ...} or the path of a lookup image
+    pub fn from_json(value: &Value) -> Option<PaletteSwap> {
+        match value {
+            Value::String(path) => Some(PaletteSwap::Image(path.clone())),
+            Value::Object(colors) => {
+                let mut map: Vec<(u32, u32)> = colors.iter()
+                    .filter_map(|(from, to)| {
+                        let pair = parse_hex_color(from).zip(to.as_str().and_then(parse_hex_color));
+                        if pair.is_none() {
+                            eprintln!("Invalid palette entry {}: {}", from, to);
+                        }
+                        pair
+                    })
+                    .collect();
+                map.sort();
+                Some(PaletteSwap::Map(map))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Identifies a sprite by its source image and the transform applied to it
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SpriteKey {
+    pub path: String,
+    // Sub-rectangle (x, y, width, height) of the source image in unscaled pixels
+    pub region: Option<(u32, u32, u32, u32)>,
+    pub scale: u32,
+    pub pivot: Option<Pivot>,
+    // Crop transparent borders, moving the pivot so the art still lands where it did untrimmed
+    pub trim: bool,
+    pub palette: Option<PaletteSwap>,
+}
+
+impl SpriteKey {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            region: None,
+            scale: 1,
+            pivot: None,
+            trim: false,
+            palette: None,
+        }
+    }
+    
+    // Narrows the current region, so frames can be cut out of a sprite that is itself a region
+    pub fn region(mut self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let (origin_x, origin_y) = self.region.map(|(rx, ry, _, _)| (rx, ry)).unwrap_or((0, 0));
+        self.region = Some((origin_x + x, origin_y + y, width, height));
+        self
+    }
+    
+    pub fn scale(mut self, scale: u32) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+    
+    pub fn named_pivot(mut self, name: &str) -> Self {
+        self.pivot = Some(Pivot::Named(name.to_string()));
+        self
+    }
+    
+    pub fn pivot_at(mut self, x: i32, y: i32) -> Self {
+        self.pivot = Some(Pivot::At(x, y));
+        self
+    }
+    
+    pub fn trimmed(mut self) -> Self {
+        self.trim = true;
+        self
+    }
+    
+    pub fn palette(mut self, palette: PaletteSwap) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+    
+    fn is_source(&self) -> bool {
+        self.region.is_none() && self.scale == 1 && self.pivot.is_none() && !self.trim && self.palette.is_none()
+    }
+}
+
+// Where a packed image ended up inside an atlas page
+#[derive(Clone, Debug)]
+struct AtlasEntry {
+    page: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+pub struct Assets {
+    sprites: HashMap<SpriteKey, Handle<Sprite>>,
+    // Original image path to its place in an atlas page
+    atlas: HashMap<String, AtlasEntry>,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self {
+            sprites: HashMap::with_capacity(64),
+            atlas: HashMap::new(),
+        }
+    }
+    
+    // Reads a manifest written by the pack_atlas tool. From then on, loading any image that was
+    // packed gives a view into its atlas page instead of decoding the loose file
+    pub fn load_atlas(&mut self, manifest_path: &str) -> usize {
+        let manifest: Value = match std::fs::read_to_string(manifest_path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Failed to parse JSON {}: {}", manifest_path, e);
+                    return 0;
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to read JSON file {}: {}", manifest_path, e);
+                return 0;
+            }
+        };
+        
+        // Page file names are relative to the manifest
+        let base_dir = Path::new(manifest_path).parent().unwrap_or(Path::new(""));
+        let pages: Vec<String> = manifest["pages"].as_array()
+            .map(|pages| pages.iter()
+                .filter_map(|p| p.as_str())
+                .map(|p| base_dir.join(p).to_string_lossy().replace('\\', "/"))
+                .collect())
+            .unwrap_or_default();
+        
+        let mut count = 0;
+        if let Some(sprites) = manifest["sprites"].as_object() {
+            for (name, info) in sprites {
+                let page = match info["page"].as_u64().and_then(|p| pages.get(p as usize)) {
+                    Some(page) => page.clone(),
+                    None => {
+                        eprintln!("Atlas entry {} in {} points at a missing page", name, manifest_path);
+                        continue;
+                    }
+                };
+                
+                let field = |key: &str| info[key].as_u64().unwrap_or(0) as u32;
+                self.atlas.insert(name.clone(), AtlasEntry {
+                    page,
+                    x: field("x"),
+                    y: field("y"),
+                    width: field("width"),
+                    height: field("height"),
+                });
+                count += 1;
+            }
+        }
+        count
+    }
+    
+    // Decodes each image once and builds each transform of it once, later calls share the result
+    pub fn sprite(&mut self, key: SpriteKey) -> Handle<Sprite> {
+        if let Some(handle) = self.sprites.get(&key) {
+            return handle.clone();
+        }
+        
+        let sprite = self.build_sprite(&key);
+        let handle = Handle::new(sprite);
+        self.sprites.insert(key, handle.clone());
+        handle
+    }
+    
+    // Rebuilds every cached sprite made from `path` in place, so existing handles show the new pixels.
+    // A changed atlas page or palette image also rebuilds everything using it.
+    // Returns false when nothing loaded from that file
+    pub fn reload(&mut self, path: &Path) -> bool {
+        let mut keys: Vec<SpriteKey> = self.sprites.keys()
+            .filter(|key| {
+                Path::new(&key.path) == path
+                    || self.atlas.get(&key.path).is_some_and(|entry| Path::new(&entry.page) == path)
+                    || matches!(&key.palette, Some(PaletteSwap::Image(image)) if Path::new(image) == path)
+            })
+            .cloned()
+            .collect();
+        if keys.is_empty() {
+            return false;
+        }
+        
+        // Everything has to be rebuilt after what it is derived from
+        keys.sort_by_key(|key| self.build_order(key));
+        for key in keys {
+            let sprite = self.build_sprite(&key);
+            if let Some(handle) = self.sprites.get(&key) {
+                *handle.0.borrow_mut() = sprite;
+            }
+        }
+        true
+    }
+    
+    fn build_order(&self, key: &SpriteKey) -> u8 {
+        if key.is_source() {
+            // Atlas pages before the images packed into them
+            self.atlas.contains_key(&key.path) as u8
+        } else if key.region.is_none() {
+            2
+        } else {
+            3
+        }
+    }
+    
+    fn build_sprite(&mut self, key: &SpriteKey) -> Sprite {
+        if key.is_source() {
+            return match self.atlas.get(&key.path).cloned() {
+                Some(entry) => self.sprite(SpriteKey::new(&entry.page)).get()
+                    .region(entry.x, entry.y, entry.width, entry.height),
+                None => Sprite::load(&key.path),
+            };
+        }
+        
+        let mut sprite = match key.region {
+            Some((x, y, width, height)) => {
+                // Cut regions out of the scaled image so frames of a sheet stay views into one buffer
+                let scale = key.scale;
+                self.sprite(SpriteKey::new(&key.path).scale(scale)).get()
+                    .region(x * scale, y * scale, width * scale, height * scale)
+            }
+            None => self.sprite(SpriteKey::new(&key.path)).get().scale(key.scale),
+        };
+        
+        match &key.pivot {
+            Some(Pivot::Named(name)) => sprite = sprite.with_named_pivot(name),
+            Some(Pivot::At(x, y)) => sprite.set_pivot(*x, *y),
+            None => {}
+        }
+        
+        if key.trim
+            && let Some((x, y, width, height)) = sprite.opaque_bounds() {
+            let pivot = (sprite.pivot_x - x as i32, sprite.pivot_y - y as i32);
+            sprite = sprite.region(x, y, width, height).with_pivot(pivot.0, pivot.1);
+        }
+        
+        if let Some(palette) = &key.palette {
+            let map = self.palette_map(palette);
+            sprite = sprite.recolor(&map);
+        }
+        sprite
+    }
+    
+    fn palette_map(&mut self, palette: &PaletteSwap) -> HashMap<u32, u32> {
+        match palette {
+            PaletteSwap::Map(pairs) => pairs.iter()
+                .map(|&(from, to)| (from & 0x00FFFFFF, to & 0x00FFFFFF))
+                .collect(),
+            PaletteSwap::Image(path) => {
+                let image = self.sprite(SpriteKey::new(path));
+                let image = image.get();
+                if image.height() < 2 {
+                    eprintln!("Palette image {} needs a row of source colors and a row of replacements", path);
+                    return HashMap::new();
+                }
+                
+                (0..image.width())
+                    .map(|x| (image.pixel(x, 0), image.pixel(x, 1)))
+                    // Fully transparent cells pad out the rows and map nothing
+                    .filter(|&(from, to)| from >> 24 != 0 && to >> 24 != 0)
+                    .map(|(from, to)| (from & 0x00FFFFFF, to & 0x00FFFFFF))
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Assets, Handle, PaletteSwap, SpriteKey};
+    use crate::sprite::Sprite;
+    use serde_json::json;
+    
+    // Caches an in-memory image under `path` as if it had been decoded from disk
+    fn assets_with(path: &str, sprite: Sprite) -> Assets {
+        let mut assets = Assets::new();
+        assets.sprites.insert(SpriteKey::new(path), Handle::new(sprite));
+        assets
+    }
+    
+    #[test]
+    fn palette_map_parses_and_skips_bad_entries() {
+        let palette = PaletteSwap::from_json(&json!({"#00FF00": "#FF0000", "#0000FF": "80FFFFFF", "nope": "#000000"}));
+        assert_eq!(palette, Some(PaletteSwap::Map(vec![(0xFF0000FF, 0x80FFFFFF), (0xFF00FF00, 0xFFFF0000)])));
+        assert_eq!(PaletteSwap::from_json(&json!("palettes/red.png")), Some(PaletteSwap::Image("palettes/red.png".to_string())));
+        assert_eq!(PaletteSwap::from_json(&json!(null)), None);
+    }
+    
+    #[test]
+    fn map_swap_keeps_source_alpha() {
+        let mut assets = assets_with("bug.png", Sprite::new(3, 1, vec![0xFF00FF00, 0x8000FF00, 0xFF123456]));
+        let palette = PaletteSwap::Map(vec![(0xFF00FF00, 0xFFFF0000)]);
+        let recolored = assets.sprite(SpriteKey::new("bug.png").palette(palette));
+        let recolored = recolored.get();
+        
+        assert_eq!(recolored.pixel(0, 0), 0xFFFF0000);
+        assert_eq!(recolored.pixel(1, 0), 0x80FF0000);
+        // Colors missing from the map are left alone
+        assert_eq!(recolored.pixel(2, 0), 0xFF123456);
+    }
+    
+    #[test]
+    fn image_swap_reads_source_and_replacement_rows() {
+        let mut assets = assets_with("bug.png", Sprite::new(2, 1, vec![0xFF00FF00, 0xFF0000FF]));
+        // Second column is padding and maps nothing
+        assets.sprites.insert(SpriteKey::new("red.png"), Handle::new(Sprite::new(2, 2, vec![
+            0xFF00FF00, 0x00000000,
+            0xFFFF0000, 0xFFFFFFFF,
+        ])));
+        
+        let recolored = assets.sprite(SpriteKey::new("bug.png").palette(PaletteSwap::Image("red.png".to_string())));
+        let recolored = recolored.get();
+        assert_eq!(recolored.pixel(0, 0), 0xFFFF0000);
+        assert_eq!(recolored.pixel(1, 0), 0xFF0000FF);
+    }
+}
@@ -0,0 +1,198 @@
+// Packs PNGs into atlas pages plus a JSON manifest the game loads through Assets::load_atlas.
+//
+//     cargo run --bin pack_atlas -- assets/atlas/sprites assets/sprites/player assets/sprites/bug.png
+//
+// writes assets/atlas/sprites.json and assets/atlas/sprites_0.png, sprites_1.png, ...
+// Sprites are named by the path they were found at, so code loading that path gets the atlas copy.
+use image::{GenericImage, RgbaImage};
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PAGE_SIZE: u32 = 1024;
+const DEFAULT_PADDING: u32 = 1;
+
+struct Input {
+    name: String,
+    image: RgbaImage,
+}
+
+struct Placement {
+    page: usize,
+    x: u32,
+    y: u32,
+}
+
+// Shelf packer: images go left to right on the current row, a new row starts below the
+// tallest image of the previous one and a new page starts once a row no longer fits
+struct Page {
+    width: u32,
+    height: u32,
+    cursor_x: u32,
+    cursor_y: u32,
+    row_height: u32,
+}
+
+impl Page {
+    fn new(width: u32, height: u32) -> Self {
+        Self { width, height, cursor_x: 0, cursor_y: 0, row_height: 0 }
+    }
+    
+    fn place(&mut self, width: u32, height: u32, padding: u32) -> Option<(u32, u32)> {
+        if self.cursor_x + width > self.width {
+            self.cursor_x = 0;
+            self.cursor_y += self.row_height + padding;
+            self.row_height = 0;
+        }
+        if self.cursor_x + width > self.width || self.cursor_y + height > self.height {
+            return None;
+        }
+        
+        let position = (self.cursor_x, self.cursor_y);
+        self.cursor_x += width + padding;
+        self.row_height = self.row_height.max(height);
+        Some(position)
+    }
+    
+    // Pages are cropped to what was used so small atlases do not waste memory
+    fn used_size(&self) -> (u32, u32) {
+        let used_height = self.cursor_y + self.row_height;
+        (self.width, used_height.max(1))
+    }
+}
+
+fn collect_pngs(path: &Path, out: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = match std::fs::read_dir(path) {
+            Ok(entries) => entries.flatten().map(|e| e.path()).collect(),
+            Err(e) => {
+                eprintln!("Failed to read directory {}: {}", path.display(), e);
+                return;
+            }
+        };
+        entries.sort();
+        for entry in entries {
+            collect_pngs(&entry, out);
+        }
+    } else if path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("png")) {
+        out.push(path.to_path_buf());
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut output = None;
+    let mut inputs = Vec::new();
+    let mut page_size = DEFAULT_PAGE_SIZE;
+    let mut padding = DEFAULT_PADDING;
+    
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => page_size = args.next().and_then(|v| v.parse().ok()).unwrap_or(page_size),
+            "--padding" => padding = args.next().and_then(|v| v.parse().ok()).unwrap_or(padding),
+            _ if output.is_none() => output = Some(arg),
+            _ => inputs.push(arg),
+        }
+    }
+    
+    let output = match output {
+        Some(output) if !inputs.is_empty() => output,
+        _ => {
+            eprintln!("Usage: pack_atlas <output_stem> <png or directory>... [--size 1024] [--padding 1]");
+            std::process::exit(1);
+        }
+    };
+    
+    let mut paths = Vec::new();
+    for input in &inputs {
+        collect_pngs(Path::new(input), &mut paths);
+    }
+    
+    let mut images: Vec<Input> = paths.iter().filter_map(|path| {
+        match image::open(path) {
+            Ok(img) => Some(Input {
+                name: path.to_string_lossy().replace('\\', "/"),
+                image: img.to_rgba8(),
+            }),
+            Err(e) => {
+                eprintln!("Skipping {}: {}", path.display(), e);
+                None
+            }
+        }
+    }).collect();
+    
+    // Tallest first keeps the shelves tight
+    images.sort_by(|a, b| b.image.height().cmp(&a.image.height()).then_with(|| a.name.cmp(&b.name)));
+    
+    let mut pages: Vec<Page> = Vec::new();
+    let mut placements = Vec::with_capacity(images.len());
+    
+    for input in &images {
+        let (width, height) = input.image.dimensions();
+        
+        let placed = pages.len().checked_sub(1).and_then(|index| {
+            pages[index].place(width, height, padding).map(|(x, y)| (index, x, y))
+        });
+        
+        let (page, x, y) = match placed {
+            Some(placed) => placed,
+            None => {
+                // Oversized images get a page of their own
+                let mut page = Page::new(page_size.max(width), page_size.max(height));
+                let (x, y) = page.place(width, height, padding).expect("a fresh page fits the image");
+                pages.push(page);
+                (pages.len() - 1, x, y)
+            }
+        };
+        placements.push(Placement { page, x, y });
+    }
+    
+    let output_path = Path::new(&output);
+    if let Some(dir) = output_path.parent()
+        && let Err(e) = std::fs::create_dir_all(dir) {
+        eprintln!("Failed to create {}: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+    let stem = output_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or(output.clone());
+    
+    let mut page_images: Vec<RgbaImage> = pages.iter()
+        .map(|page| {
+            let (width, height) = page.used_size();
+            RgbaImage::new(width, height)
+        })
+        .collect();
+    
+    let mut sprites = Map::new();
+    for (input, placement) in images.iter().zip(&placements) {
+        page_images[placement.page]
+            .copy_from(&input.image, placement.x, placement.y)
+            .expect("placement lies inside the page");
+        sprites.insert(input.name.clone(), json!({
+            "page": placement.page,
+            "x": placement.x,
+            "y": placement.y,
+            "width": input.image.width(),
+            "height": input.image.height(),
+        }));
+    }
+    
+    let mut page_names = Vec::with_capacity(page_images.len());
+    for (index, page) in page_images.iter().enumerate() {
+        let name = format!("{}_{}.png", stem, index);
+        let path = output_path.with_file_name(&name);
+        if let Err(e) = page.save(&path) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+        page_names.push(Value::String(name));
+    }
+    
+    let manifest = json!({ "pages": page_names, "sprites": sprites });
+    let manifest_path = output_path.with_file_name(format!("{}.json", stem));
+    let text = serde_json::to_string_pretty(&manifest).expect("manifest serializes");
+    if let Err(e) = std::fs::write(&manifest_path, text) {
+        eprintln!("Failed to write {}: {}", manifest_path.display(), e);
+        std::process::exit(1);
+    }
+    
+    println!("Packed {} sprites into {} page(s), manifest {}", images.len(), page_names.len(), manifest_path.display());
+}
\ No newline at end of file
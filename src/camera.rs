@@ -1,32 +1,478 @@
-#[derive(Debug, Clone)]
-pub struct Camera {
-    pub x: i32,
-    pub y: i32,
-    pub viewport_width: i32,
-    pub viewport_height: i32,
-}
-
-impl Camera {
-    pub fn new(viewport_width: i32, viewport_height: i32) -> Self {
-        Self {
-            x: 0,
-            y: 0,
-            viewport_width,
-            viewport_height,
-        }
-    }
-    
-    pub fn follow(&mut self, target_x: i32, target_y: i32) {
-        self.x = target_x - self.viewport_width / 2;
-        self.y = target_y - self.viewport_height / 2;
-    }
-    
-    pub fn follow_smooth(&mut self, target_x: i32, target_y: i32, modifier: f32) {
-        let target_x = target_x - self.viewport_width / 2;
-        let target_y = target_y - self.viewport_height / 2;
-        let dx = target_x - self.x;
-        let dy = target_y - self.y;
-        self.x += (dx as f32 * modifier) as i32;
-        self.y += (dy as f32 * modifier) as i32;
-    }
-}
\ No newline at end of file
+use serde_json::Value;
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+    
+    // Maps linear progress 0..1 to eased progress 0..1, cubic curves
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => if t < 0.5 {
+                4.0 * t * t * t
+            } else {
+                1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+            },
+        }
+    }
+}
+
+// Scripted camera movement, positions are the world point to centre on
+#[derive(Clone, Debug)]
+pub enum CameraMove {
+    PanTo { x: f32, y: f32, duration: f32, easing: Easing },
+    // Through every point in turn at an even speed, the easing applies to the whole path
+    FollowPath { points: Vec<(f32, f32)>, duration: f32, easing: Easing },
+    Hold { duration: f32 },
+}
+
+impl CameraMove {
+    fn duration(&self) -> f32 {
+        match self {
+            CameraMove::PanTo { duration, .. }
+            | CameraMove::FollowPath { duration, .. }
+            | CameraMove::Hold { duration } => *duration,
+        }
+    }
+}
+
+// What gameplay code and Lua scripts can ask of the camera
+#[derive(Clone, Debug)]
+pub enum CameraCommand {
+    Move(CameraMove),
+    Shake(f32),
+    Zoom(f32),
+    // Drops queued moves and goes back to following the player
+    Release,
+}
+
+#[derive(Debug, Clone)]
+struct ActiveMove {
+    movement: CameraMove,
+    // View centre when the move started
+    start: (f32, f32),
+    elapsed: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Camera {
+    // Top-left corner of the view in world pixels, kept fractional so slow movement
+    // does not stall or jitter, rounded only when handed to the renderer
+    pub x: f32,
+    pub y: f32,
+    pub viewport_width: i32,
+    pub viewport_height: i32,
+    // Where the view sits on the logical screen, e.g. the right half in split-screen
+    pub viewport_x: i32,
+    pub viewport_y: i32,
+    // Rectangle around the view centre the target can move in without the camera following
+    pub deadzone_width: f32,
+    pub deadzone_height: f32,
+    // Seconds of target velocity to lead by, so more of the way ahead is on screen
+    pub look_ahead: f32,
+    // Seconds to close half the distance to where the camera wants to be, 0 snaps
+    pub half_life: f32,
+    // (left, top, right, bottom) of the level, the view never shows past them
+    pub bounds: Option<(f32, f32, f32, f32)>,
+    // Screen pixels per world pixel, eased towards zoom_target
+    pub zoom: f32,
+    pub zoom_target: f32,
+    pub zoom_levels: Vec<f32>,
+    // 0..1, shake strength is trauma squared so small knocks stay subtle
+    pub trauma: f32,
+    // Trauma lost per second
+    pub trauma_decay: f32,
+    // Offset in world pixels at full trauma
+    pub max_shake: f32,
+    pub shake_frequency: f32,
+    shake_time: f32,
+    moves: VecDeque<CameraMove>,
+    active_move: Option<ActiveMove>,
+}
+
+impl Camera {
+    pub fn new(viewport_width: i32, viewport_height: i32) -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            viewport_width,
+            viewport_height,
+            viewport_x: 0,
+            viewport_y: 0,
+            deadzone_width: 0.0,
+            deadzone_height: 0.0,
+            look_ahead: 0.0,
+            half_life: 0.1,
+            bounds: None,
+            zoom: 1.0,
+            zoom_target: 1.0,
+            zoom_levels: vec![1.0],
+            trauma: 0.0,
+            trauma_decay: 1.5,
+            max_shake: 10.0,
+            shake_frequency: 15.0,
+            shake_time: 0.0,
+            moves: VecDeque::new(),
+            active_move: None,
+        }
+    }
+    
+    // Reads deadzone, look_ahead, half_life, bounds, zoom_levels and shake, fields left out keep their current value
+    pub fn load_settings(&mut self, path: &str) -> bool {
+        let settings: Value = match std::fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Failed to parse JSON {}: {}", path, e);
+                    return false;
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to read JSON file {}: {}", path, e);
+                return false;
+            }
+        };
+        
+        let number = |value: &Value| value.as_f64().map(|v| v as f32);
+        if let Some(width) = number(&settings["deadzone"][0]) {
+            self.deadzone_width = width.max(0.0);
+        }
+        if let Some(height) = number(&settings["deadzone"][1]) {
+            self.deadzone_height = height.max(0.0);
+        }
+        if let Some(look_ahead) = number(&settings["look_ahead"]) {
+            self.look_ahead = look_ahead.max(0.0);
+        }
+        if let Some(half_life) = number(&settings["half_life"]) {
+            self.half_life = half_life.max(0.0);
+        }
+        if let Some(levels) = settings["zoom_levels"].as_array() {
+            let levels: Vec<f32> = levels.iter().filter_map(number).filter(|&zoom| zoom > 0.0).collect();
+            if !levels.is_empty() {
+                self.zoom_levels = levels;
+            }
+        }
+        
+        let shake = &settings["shake"];
+        if let Some(max_offset) = number(&shake["max_offset"]) {
+            self.max_shake = max_offset.max(0.0);
+        }
+        if let Some(decay) = number(&shake["decay"]) {
+            self.trauma_decay = decay.max(0.0);
+        }
+        if let Some(frequency) = number(&shake["frequency"]) {
+            self.shake_frequency = frequency.max(0.0);
+        }
+        
+        match &settings["bounds"] {
+            Value::Array(edges) if edges.len() == 4 => {
+                let edges: Vec<f32> = edges.iter().filter_map(number).collect();
+                if edges.len() == 4 && edges[0] < edges[2] && edges[1] < edges[3] {
+                    self.bounds = Some((edges[0], edges[1], edges[2], edges[3]));
+                } else {
+                    eprintln!("Camera bounds in {} should be [left, top, right, bottom]", path);
+                }
+            }
+            Value::Null => self.bounds = None,
+            _ => eprintln!("Camera bounds in {} should be [left, top, right, bottom]", path),
+        }
+        true
+    }
+    
+    // World pixels covered by the view at the current zoom
+    pub fn view_size(&self) -> (f32, f32) {
+        (self.viewport_width as f32 / self.zoom, self.viewport_height as f32 / self.zoom)
+    }
+    
+    pub fn center(&self) -> (f32, f32) {
+        let (view_width, view_height) = self.view_size();
+        (self.x + view_width / 2.0, self.y + view_height / 2.0)
+    }
+    
+    fn set_center(&mut self, center_x: f32, center_y: f32) {
+        let (view_width, view_height) = self.view_size();
+        self.x = center_x - view_width / 2.0;
+        self.y = center_y - view_height / 2.0;
+        self.clamp_to_bounds();
+    }
+    
+    // Centres on the target straight away, e.g. on spawn or after a teleport
+    pub fn follow(&mut self, target_x: i32, target_y: i32) {
+        self.set_center(target_x as f32, target_y as f32);
+    }
+    
+    // Eases towards the target, `delta` in seconds and velocity in pixels per second.
+    // The same distance is covered in the same time whatever the frame rate.
+    // Does nothing while a scripted move has the camera
+    pub fn follow_smooth(&mut self, target_x: f32, target_y: f32, velocity_x: f32, velocity_y: f32, delta: f32) {
+        if self.is_scripted() {
+            return;
+        }
+        
+        let goal_x = target_x + velocity_x * self.look_ahead;
+        let goal_y = target_y + velocity_y * self.look_ahead;
+        let (center_x, center_y) = self.center();
+        
+        // Only the part of the goal outside the deadzone moves the camera
+        let desired_x = center_x + deadzone_overshoot(goal_x - center_x, self.deadzone_width / 2.0);
+        let desired_y = center_y + deadzone_overshoot(goal_y - center_y, self.deadzone_height / 2.0);
+        
+        let t = self.smoothing(delta);
+        self.x += (desired_x - center_x) * t;
+        self.y += (desired_y - center_y) * t;
+        self.clamp_to_bounds();
+    }
+    
+    // Fraction of the remaining distance to cover this frame
+    fn smoothing(&self, delta: f32) -> f32 {
+        if self.half_life <= 0.0 {
+            1.0
+        } else {
+            1.0 - 0.5f32.powf(delta.max(0.0) / self.half_life)
+        }
+    }
+    
+    // Advances shake, zoom and scripted moves, call once per frame before follow_smooth
+    pub fn update(&mut self, delta: f32) {
+        self.trauma = (self.trauma - self.trauma_decay * delta).max(0.0);
+        self.shake_time += delta;
+        
+        if self.zoom != self.zoom_target {
+            // Zoom about the view centre
+            let (center_x, center_y) = self.center();
+            self.zoom += (self.zoom_target - self.zoom) * self.smoothing(delta);
+            if (self.zoom - self.zoom_target).abs() < 0.001 {
+                self.zoom = self.zoom_target;
+            }
+            self.set_center(center_x, center_y);
+        }
+        
+        self.update_moves(delta);
+    }
+    
+    pub fn apply(&mut self, command: CameraCommand) {
+        match command {
+            CameraCommand::Move(movement) => self.queue_move(movement),
+            CameraCommand::Shake(amount) => self.add_trauma(amount),
+            CameraCommand::Zoom(zoom) => self.zoom_to(zoom),
+            CameraCommand::Release => {
+                self.moves.clear();
+                self.active_move = None;
+            }
+        }
+    }
+    
+    // Hits add trauma, it stacks up to 1 and wears off over time
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+    
+    pub fn zoom_to(&mut self, zoom: f32) {
+        if zoom > 0.0 {
+            self.zoom_target = zoom;
+        }
+    }
+    
+    // Steps through zoom_levels, +1 zooms in and -1 out
+    pub fn step_zoom(&mut self, steps: i32) {
+        let current = self.zoom_levels.iter()
+            .position(|&level| level >= self.zoom_target)
+            .unwrap_or(self.zoom_levels.len() - 1) as i32;
+        let index = (current + steps).clamp(0, self.zoom_levels.len() as i32 - 1);
+        self.zoom_to(self.zoom_levels[index as usize]);
+    }
+    
+    // Moves play one after another, the player is followed again once the queue runs out
+    pub fn queue_move(&mut self, movement: CameraMove) {
+        self.moves.push_back(movement);
+    }
+    
+    pub fn is_scripted(&self) -> bool {
+        self.active_move.is_some() || !self.moves.is_empty()
+    }
+    
+    fn update_moves(&mut self, delta: f32) {
+        let mut remaining = delta;
+        loop {
+            if self.active_move.is_none() {
+                let movement = match self.moves.pop_front() {
+                    Some(movement) => movement,
+                    None => return,
+                };
+                self.active_move = Some(ActiveMove { movement, start: self.center(), elapsed: 0.0 });
+            }
+            
+            let active = self.active_move.as_mut().expect("a move is active");
+            let duration = active.movement.duration();
+            active.elapsed += remaining;
+            let progress = if duration <= 0.0 { 1.0 } else { (active.elapsed / duration).min(1.0) };
+            
+            let target = match &active.movement {
+                CameraMove::PanTo { x, y, easing, .. } => {
+                    let t = easing.apply(progress);
+                    Some((active.start.0 + (x - active.start.0) * t, active.start.1 + (y - active.start.1) * t))
+                }
+                CameraMove::FollowPath { points, easing, .. } => {
+                    Some(point_along_path(active.start, points, easing.apply(progress)))
+                }
+                CameraMove::Hold { .. } => None,
+            };
+            if let Some((x, y)) = target {
+                self.set_center(x, y);
+            }
+            
+            // Time left over after finishing a move goes to the next one
+            let active = self.active_move.as_ref().expect("a move is active");
+            if progress < 1.0 {
+                return;
+            }
+            remaining = (active.elapsed - duration).max(0.0);
+            self.active_move = None;
+        }
+    }
+    
+    // Whole-pixel offset for drawing, shake included
+    pub fn render_offset(&self) -> (i32, i32) {
+        let (shake_x, shake_y) = self.shake_offset();
+        ((self.x + shake_x).round() as i32, (self.y + shake_y).round() as i32)
+    }
+    
+    // Point of the logical screen (the renderer's buffer, not the window) to world pixels,
+    // using the same rounded and shaken offset the frame was drawn with
+    pub fn screen_to_world(&self, screen_x: f32, screen_y: f32) -> (f32, f32) {
+        let (offset_x, offset_y) = self.render_offset();
+        (
+            offset_x as f32 + (screen_x - self.viewport_x as f32) / self.zoom,
+            offset_y as f32 + (screen_y - self.viewport_y as f32) / self.zoom,
+        )
+    }
+    
+    pub fn world_to_screen(&self, world_x: f32, world_y: f32) -> (f32, f32) {
+        let (offset_x, offset_y) = self.render_offset();
+        (
+            self.viewport_x as f32 + (world_x - offset_x as f32) * self.zoom,
+            self.viewport_y as f32 + (world_y - offset_y as f32) * self.zoom,
+        )
+    }
+    
+    // Smooth pseudo-noise from a few unrelated sines, so the shake wobbles instead of flickering
+    fn shake_offset(&self) -> (f32, f32) {
+        if self.trauma <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let strength = self.max_shake * self.trauma * self.trauma;
+        let phase = self.shake_time * self.shake_frequency * std::f32::consts::TAU;
+        let noise_x = phase.sin() * 0.6 + (phase * 2.3 + 1.7).sin() * 0.4;
+        let noise_y = (phase * 1.3 + 0.5).sin() * 0.6 + (phase * 2.9 + 4.1).sin() * 0.4;
+        (noise_x * strength, noise_y * strength)
+    }
+    
+    fn clamp_to_bounds(&mut self) {
+        let (left, top, right, bottom) = match self.bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        
+        // A level smaller than the view is centred instead
+        let clamp_axis = |position: f32, min: f32, max: f32, view: f32| {
+            if max - min <= view {
+                (min + max - view) / 2.0
+            } else {
+                position.clamp(min, max - view)
+            }
+        };
+        let (view_width, view_height) = self.view_size();
+        self.x = clamp_axis(self.x, left, right, view_width);
+        self.y = clamp_axis(self.y, top, bottom, view_height);
+    }
+}
+
+// Point a fraction `t` of the way along start -> points[0] -> points[1] -> ..., by length
+fn point_along_path(start: (f32, f32), points: &[(f32, f32)], t: f32) -> (f32, f32) {
+    let segments = || std::iter::once(start).chain(points.iter().copied()).zip(points.iter().copied());
+    let length = |from: (f32, f32), to: (f32, f32)| ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+    let total: f32 = segments().map(|(from, to)| length(from, to)).sum();
+    if total <= 0.0 {
+        return points.last().copied().unwrap_or(start);
+    }
+    
+    let mut distance = total * t.clamp(0.0, 1.0);
+    for (from, to) in segments() {
+        let length = length(from, to);
+        if distance <= length && length > 0.0 {
+            let f = distance / length;
+            return (from.0 + (to.0 - from.0) * f, from.1 + (to.1 - from.1) * f);
+        }
+        distance -= length;
+    }
+    points.last().copied().unwrap_or(start)
+}
+
+// How far `offset` reaches past `half_extent` on either side, 0 while inside it
+fn deadzone_overshoot(offset: f32, half_extent: f32) -> f32 {
+    if offset > half_extent {
+        offset - half_extent
+    } else if offset < -half_extent {
+        offset + half_extent
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Camera;
+    
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!((actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+    }
+    
+    #[test]
+    fn conversions_apply_zoom_and_viewport_offset() {
+        let mut camera = Camera::new(400, 300);
+        camera.x = 100.0;
+        camera.y = 50.0;
+        assert_near(camera.screen_to_world(10.0, 20.0), (110.0, 70.0));
+        
+        // Right half of a split screen, zoomed in twice
+        camera.viewport_x = 400;
+        camera.zoom = 2.0;
+        assert_near(camera.screen_to_world(410.0, 20.0), (105.0, 60.0));
+        assert_near(camera.world_to_screen(105.0, 60.0), (410.0, 20.0));
+    }
+    
+    #[test]
+    fn conversions_use_the_drawn_offset() {
+        let mut camera = Camera::new(400, 300);
+        camera.x = 100.4;
+        camera.y = 49.6;
+        // Frames are drawn at whole pixels, so the mouse maps through the rounded offset
+        assert_near(camera.screen_to_world(0.0, 0.0), (100.0, 50.0));
+        
+        camera.add_trauma(1.0);
+        camera.update(0.01);
+        let (offset_x, offset_y) = camera.render_offset();
+        assert_ne!((offset_x, offset_y), (100, 50));
+        assert_near(camera.screen_to_world(0.0, 0.0), (offset_x as f32, offset_y as f32));
+        assert_near(camera.world_to_screen(offset_x as f32 + 5.0, offset_y as f32), (5.0, 0.0));
+    }
+}
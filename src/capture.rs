@@ -0,0 +1,146 @@
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+
+pub fn to_rgba_image(pixels: &[u32], width: usize, height: usize) -> RgbaImage {
+    let mut bytes = Vec::with_capacity(width * height * 4);
+    for &color in pixels {
+        // The frame buffer is not alpha blended against anything, so treat every pixel as opaque
+        bytes.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8, 0xFF]);
+    }
+    RgbaImage::from_raw(width as u32, height as u32, bytes).expect("buffer size matches dimensions")
+}
+
+pub fn save_png(pixels: &[u32], width: usize, height: usize, path: &str) -> Result<(), image::ImageError> {
+    to_rgba_image(pixels, width, height).save(path)
+}
+
+// Number of pixels that differ from a reference PNG by more than `tolerance` on any channel,
+// None when the reference is missing or a different size
+pub fn diff_against_png(pixels: &[u32], width: usize, height: usize, path: &str, tolerance: u8) -> Option<usize> {
+    let golden = image::open(path).ok()?.to_rgba8();
+    if golden.width() as usize != width || golden.height() as usize != height {
+        return None;
+    }
+    
+    let frame = to_rgba_image(pixels, width, height);
+    let mismatched = frame.pixels().zip(golden.pixels())
+        .filter(|(a, b)| (0..3).any(|c| a[c].abs_diff(b[c]) > tolerance))
+        .count();
+    Some(mismatched)
+}
+
+#[derive(Clone)]
+struct CapturedFrame {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+pub struct FrameCapture {
+    pub output_dir: String,
+    // Write every Nth presented frame to output_dir as PNG while set
+    pub dump_every: Option<u32>,
+    // The ring keeps the last `ring_seconds` at `ring_fps`, shrunk by `ring_downscale`
+    pub ring_seconds: f32,
+    pub ring_fps: u32,
+    pub ring_downscale: usize,
+    frame_index: u64,
+    ring: VecDeque<CapturedFrame>,
+}
+
+impl FrameCapture {
+    const SOURCE_FPS: u32 = 60;
+    
+    pub fn new(output_dir: &str) -> Self {
+        Self {
+            output_dir: output_dir.to_string(),
+            dump_every: None,
+            ring_seconds: 4.0,
+            ring_fps: 15,
+            ring_downscale: 2,
+            frame_index: 0,
+            ring: VecDeque::new(),
+        }
+    }
+    
+    fn ring_capacity(&self) -> usize {
+        (self.ring_seconds * self.ring_fps as f32).ceil() as usize
+    }
+    
+    pub fn record(&mut self, pixels: &[u32], width: usize, height: usize) {
+        self.frame_index += 1;
+        
+        let dump_frame = self.dump_every
+            .is_some_and(|every| every > 0 && self.frame_index.is_multiple_of(every as u64));
+        if dump_frame {
+            let path = format!("{}/frame_{:06}.png", self.output_dir, self.frame_index);
+            if let Err(e) = std::fs::create_dir_all(&self.output_dir)
+                .map_err(image::ImageError::IoError)
+                .and_then(|_| save_png(pixels, width, height, &path)) {
+                eprintln!("Failed to write capture frame {}: {}", path, e);
+            }
+        }
+        
+        let step = (Self::SOURCE_FPS / self.ring_fps.max(1)).max(1) as u64;
+        let capacity = self.ring_capacity();
+        if capacity == 0 || !self.frame_index.is_multiple_of(step) {
+            return;
+        }
+        
+        let scale = self.ring_downscale.max(1);
+        let small_width = width / scale;
+        let small_height = height / scale;
+        
+        // Reuse the oldest frame's allocation once the ring is full
+        let mut frame = if self.ring.len() >= capacity {
+            self.ring.pop_front().unwrap()
+        } else {
+            CapturedFrame { width: 0, height: 0, pixels: Vec::new() }
+        };
+        frame.width = small_width;
+        frame.height = small_height;
+        frame.pixels.clear();
+        for y in 0..small_height {
+            let row = y * scale * width;
+            frame.pixels.extend((0..small_width).map(|x| pixels[row + x * scale]));
+        }
+        self.ring.push_back(frame);
+    }
+    
+    // Encodes the ring buffer as an animated GIF on a background thread so the game keeps running
+    pub fn save_clip(&self, path: &str) {
+        // A GIF has one canvas size, so drop anything recorded before the last window resize
+        let latest = self.ring.back().map(|f| (f.width, f.height));
+        let frames: Vec<CapturedFrame> = self.ring.iter()
+            .filter(|f| Some((f.width, f.height)) == latest)
+            .cloned()
+            .collect();
+        if frames.is_empty() {
+            eprintln!("No frames captured yet for {}", path);
+            return;
+        }
+        
+        let path = path.to_string();
+        let delay = Delay::from_numer_denom_ms(1000, self.ring_fps.max(1));
+        
+        std::thread::spawn(move || {
+            let result = File::create(&path)
+                .map_err(image::ImageError::IoError)
+                .and_then(|file| {
+                    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+                    encoder.set_repeat(Repeat::Infinite)?;
+                    encoder.encode_frames(frames.into_iter().map(|f| {
+                        Frame::from_parts(to_rgba_image(&f.pixels, f.width, f.height), 0, 0, delay)
+                    }))
+                });
+            
+            match result {
+                Ok(_) => println!("Saved clip to {}", path),
+                Err(e) => eprintln!("Failed to save clip {}: {}", path, e),
+            }
+        });
+    }
+}
\ No newline at end of file
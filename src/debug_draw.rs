@@ -0,0 +1,174 @@
+use crate::renderer::{draw_filled_rectangle_into, draw_line_into, draw_rectangle_into};
+use minifb::{Key, KeyRepeat, Window};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DebugCategory {
+    Colliders,
+    Sensors,
+    Velocity,
+    AiTargets,
+    SpatialHash,
+    EntityIds,
+}
+
+impl DebugCategory {
+    pub const ALL: [DebugCategory; 6] = [
+        DebugCategory::Colliders,
+        DebugCategory::Sensors,
+        DebugCategory::Velocity,
+        DebugCategory::AiTargets,
+        DebugCategory::SpatialHash,
+        DebugCategory::EntityIds,
+    ];
+    
+    pub fn toggle_key(&self) -> Key {
+        match self {
+            DebugCategory::Colliders => Key::F1,
+            DebugCategory::Sensors => Key::F2,
+            DebugCategory::Velocity => Key::F3,
+            DebugCategory::AiTargets => Key::F4,
+            DebugCategory::SpatialHash => Key::F5,
+            DebugCategory::EntityIds => Key::F6,
+        }
+    }
+}
+
+// Shapes are in world coordinates and get the camera applied when drawn
+pub enum DebugShape {
+    Rect { left: i32, top: i32, width: u32, height: u32, color: u32 },
+    FilledRect { left: i32, top: i32, width: u32, height: u32, color: u32 },
+    Line { x1: i32, y1: i32, x2: i32, y2: i32, color: u32 },
+    Text { x: i32, y: i32, text: String, color: u32 },
+}
+
+pub struct DebugOverlay {
+    enabled: [bool; DebugCategory::ALL.len()],
+    shapes: Vec<DebugShape>,
+}
+
+impl DebugOverlay {
+    pub const SPATIAL_CELL_SIZE: i32 = 64;
+    
+    pub fn new() -> Self {
+        Self {
+            enabled: [false; DebugCategory::ALL.len()],
+            shapes: Vec::with_capacity(256),
+        }
+    }
+    
+    pub fn is_enabled(&self, category: DebugCategory) -> bool {
+        self.enabled[category as usize]
+    }
+    
+    pub fn any_enabled(&self) -> bool {
+        self.enabled.iter().any(|&on| on)
+    }
+    
+    pub fn toggle(&mut self, category: DebugCategory) {
+        self.enabled[category as usize] = !self.enabled[category as usize];
+    }
+    
+    pub fn handle_keys(&mut self, window: &Window) {
+        for category in DebugCategory::ALL {
+            if window.is_key_pressed(category.toggle_key(), KeyRepeat::No) {
+                self.toggle(category);
+            }
+        }
+    }
+    
+    // Called at the start of every tick, shapes stay on screen until the next tick replaces them
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+    
+    pub fn rect(&mut self, category: DebugCategory, bounds: (i32, i32, i32, i32), color: u32) {
+        if self.is_enabled(category) {
+            let (left, top, right, bottom) = bounds;
+            let (width, height) = ((right - left).max(0) as u32, (bottom - top).max(0) as u32);
+            self.shapes.push(DebugShape::FilledRect { left, top, width, height, color: (color & 0x00FFFFFF) | 0x22000000 });
+            self.shapes.push(DebugShape::Rect { left, top, width, height, color });
+        }
+    }
+    
+    pub fn line(&mut self, category: DebugCategory, from: (i32, i32), to: (i32, i32), color: u32) {
+        if self.is_enabled(category) {
+            self.shapes.push(DebugShape::Line { x1: from.0, y1: from.1, x2: to.0, y2: to.1, color });
+        }
+    }
+    
+    pub fn text(&mut self, category: DebugCategory, x: i32, y: i32, text: &str, color: u32) {
+        if self.is_enabled(category) {
+            self.shapes.push(DebugShape::Text { x, y, text: text.to_string(), color });
+        }
+    }
+    
+    pub fn draw(&self, buffer: &mut [u32], buf_w: usize, buf_h: usize, camera_x: i32, camera_y: i32) {
+        for shape in &self.shapes {
+            match shape {
+                DebugShape::Rect { left, top, width, height, color } => {
+                    draw_rectangle_into(buffer, buf_w, buf_h, left - camera_x, top - camera_y, *width, *height, *color, 1);
+                }
+                DebugShape::FilledRect { left, top, width, height, color } => {
+                    draw_filled_rectangle_into(buffer, buf_w, buf_h, left - camera_x, top - camera_y, *width, *height, *color);
+                }
+                DebugShape::Line { x1, y1, x2, y2, color } => {
+                    draw_line_into(buffer, buf_w, buf_h, x1 - camera_x, y1 - camera_y, x2 - camera_x, y2 - camera_y, *color);
+                }
+                DebugShape::Text { x, y, text, color } => {
+                    draw_text(buffer, buf_w, buf_h, x - camera_x, y - camera_y, text, *color);
+                }
+            }
+        }
+    }
+}
+
+// 3x5 pixel glyphs, one bit per pixel read left to right, top to bottom
+fn glyph(c: char) -> u16 {
+    match c.to_ascii_uppercase() {
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111,
+        '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111,
+        '7' => 0b111_001_010_010_010,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_111,
+        '#' => 0b101_111_101_111_101,
+        '-' => 0b000_000_111_000_000,
+        ':' => 0b000_010_000_010_000,
+        '.' => 0b000_000_000_000_010,
+        'A' => 0b010_101_111_101_101,
+        'B' => 0b110_101_110_101_110,
+        'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111,
+        'I' => 0b111_010_010_010_111,
+        'P' => 0b111_101_111_100_100,
+        'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010,
+        _ => 0,
+    }
+}
+
+pub const GLYPH_WIDTH: i32 = 4;
+
+pub fn draw_text(buffer: &mut [u32], buf_w: usize, buf_h: usize, x: i32, y: i32, text: &str, color: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let bits = glyph(c);
+        let origin_x = x + i as i32 * GLYPH_WIDTH;
+        
+        for row in 0..5 {
+            for col in 0..3 {
+                if bits & (1 << (14 - (row * 3 + col))) == 0 {
+                    continue;
+                }
+                let px = origin_x + col;
+                let py = y + row;
+                if px >= 0 && py >= 0 && (px as usize) < buf_w && (py as usize) < buf_h {
+                    buffer[py as usize * buf_w + px as usize] = color;
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -1,125 +1,375 @@
-use crate::ecs::{ECSManager, EntityID};
-use crate::renderer::Renderer;
-use crate::sprite::Sprite;
-use crate::scripting::EnemyAI;
-use serde_json::Value;
-
-pub struct EnemyManager {
-    enemy_data: Value,
-    //pub ai: EnemyAI,
-}
-
-impl EnemyManager {
-    pub fn new(json_path: &str) -> Self {
-        let enemy_data = match std::fs::read_to_string(json_path) {
-            Ok(content) => {
-                match serde_json::from_str(&content) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        eprintln!("Failed to parse JSON {}: {}", json_path, e);
-                        Value::Object(serde_json::Map::new())
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to read JSON file {}: {}", json_path, e);
-                Value::Object(serde_json::Map::new())
-            }
-        };
-        
-        //let mut ai = EnemyAI::new();
-        
-        // Try to load AI scripts, but don't crash if it fails
-        //if let Err(e) = ai.load_all_ai_scripts("assets/scripts") {
-        //    eprintln!("Warning: Failed to load AI scripts: {}", e);
-        //}
-        
-        EnemyManager { enemy_data } // EnemyManager { enemy_data, ai }
-    }
-    
-    pub fn spawn_enemy(
-        &self,
-        enemy_type: &str,
-        ecs_manager: &mut ECSManager,
-        renderer: &mut Renderer,
-        x: i32,
-        y: i32,
-    ) -> Option<EntityID> {
-        if let Some(enemy_info) = self.enemy_data.get(enemy_type) {
-            if let Some(sprite_path) = enemy_info["sprite"].as_str() {
-                // Use a fallback sprite if the specified one doesn't exist
-                let actual_sprite_path = if std::path::Path::new(sprite_path).exists() {
-                    sprite_path
-                } else {
-                    "assets/sprites/missing_texture.png"
-                };
-                
-                let scale = enemy_info["scale"].as_u64().unwrap_or(1) as u32;
-                let sprite = Sprite::load(actual_sprite_path).scale(scale);
-                
-                let entity_id = ecs_manager.create_entity(enemy_type, x, y, 1);
-                if let Some(entity) = ecs_manager.get_entity_mut(entity_id) {
-                    entity.set_collider_centered(32, 32);
-                    entity.simple_collisions = true;
-                    entity.set_sprite(sprite.clone());
-                    entity.solid = true;
-                    entity.pushable = enemy_info["pushable"].as_bool().unwrap_or(true);
-                    entity.mass = enemy_info["mass"].as_f64().unwrap_or(1.0) as f32;
-                    
-                    renderer.add_sprite_instance(entity_id, crate::renderer::SpriteInstance {
-                        sprite,
-                        position_x: x,
-                        position_y: y,
-                        z_order: 1,
-                        flip_horizontal: false,
-                    });
-                    
-                    println!("Spawned {} at ({}, {})", enemy_type, x, y);
-                    return Some(entity_id);
-                }
-            }
-        }
-        
-        eprintln!("Failed to spawn enemy type: {}", enemy_type);
-        None
-    }
-    
-    pub fn get_enemy_stats(&self, enemy_type: &str) -> Option<EnemyStats> {
-        self.enemy_data.get(enemy_type).map(|enemy_info| {
-            EnemyStats {
-                health: enemy_info["health"].as_u64().unwrap_or(100) as i32,
-                max_health: enemy_info["max_health"].as_u64().unwrap_or(100) as i32,
-                speed: enemy_info["speed"].as_f64().unwrap_or(3.0) as f32,
-                damage: enemy_info["damage"].as_u64().unwrap_or(20) as i32,
-                experience: enemy_info["experience"].as_u64().unwrap_or(0) as i32,
-                aggro_range: enemy_info["aggro_range"].as_u64().unwrap_or(300) as i32,
-                attack_range: enemy_info["attack_range"].as_u64().unwrap_or(50) as i32,
-                attack_cooldown: enemy_info["attack_cooldown"].as_f64().unwrap_or(1.5) as f32,
-                knockback_resistance: enemy_info["knockback_resistance"].as_f64().unwrap_or(0.0) as f32,
-                ai_type: enemy_info["ai_type"].as_str().unwrap_or("patrol").to_string(),
-                pushable: enemy_info["pushable"].as_bool().unwrap_or(true),
-                mass: enemy_info["mass"].as_f64().unwrap_or(1.0) as f32,
-                scale: enemy_info["scale"].as_u64().unwrap_or(1) as u32,
-                attack_sequence: vec![20],
-            }
-        })
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct EnemyStats {
-    pub health: i32,
-    pub max_health: i32,
-    pub speed: f32,
-    pub damage: i32,
-    pub experience: i32,
-    pub aggro_range: i32,
-    pub attack_range: i32,
-    pub attack_cooldown: f32,
-    pub knockback_resistance: f32,
-    pub ai_type: String,
-    pub pushable: bool,
-    pub mass: f32,
-    pub scale: u32,
-    pub attack_sequence: Vec<i32>,
+use crate::ecs::{ECSManager, EntityID};
+use crate::renderer::Renderer;
+use crate::assets::{Assets, PaletteSwap, SpriteKey};
+use crate::animation_manager::{AnimationHandler, Direction, GridLayout, LoopDirection};
+use crate::animation_state::{AnimationState, AnimationStateMachine};
+use crate::scripting::EnemyAI;
+use crate::lighting::PointLight;
+use crate::helper_methods::parse_hex_color;
+use crate::render_layers::Layer;
+use serde_json::Value;
+
+// Animation playback of one enemy. Frames are shared with every enemy of the type through Assets
+pub struct EnemyAnimator {
+    pub animations: AnimationHandler,
+    // AI and combat set "speed", "attack", "is_hurt" and "is_dead" on this
+    pub states: AnimationStateMachine,
+}
+
+impl EnemyAnimator {
+    pub fn update(&mut self, delta: f32) {
+        self.states.update(&mut self.animations, delta);
+    }
+}
+
+pub struct EnemyManager {
+    enemy_data: Value,
+    json_path: String,
+    pub ai: EnemyAI,
+}
+
+impl EnemyManager {
+    const MAX_VARIANT_DEPTH: usize = 8;
+    const DEFAULT_FRAME_DURATION: f64 = 0.1;
+    
+    pub fn new(json_path: &str) -> Self {
+        let enemy_data = Self::load_enemy_data(json_path)
+            .unwrap_or_else(|| Value::Object(serde_json::Map::new()));
+        
+        let mut ai = EnemyAI::new();
+        
+        // Try to load AI scripts, but don't crash if it fails
+        if let Err(e) = ai.load_all_ai_scripts("assets/scripts") {
+            eprintln!("Warning: Failed to load AI scripts: {}", e);
+        }
+        
+        EnemyManager { enemy_data, json_path: json_path.to_string(), ai }
+    }
+    
+    fn load_enemy_data(json_path: &str) -> Option<Value> {
+        match std::fs::read_to_string(json_path) {
+            Ok(content) => {
+                match serde_json::from_str(&content) {
+                    Ok(data) => Some(Self::resolve_variants(data)),
+                    Err(e) => {
+                        eprintln!("Failed to parse JSON {}: {}", json_path, e);
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to read JSON file {}: {}", json_path, e);
+                None
+            }
+        }
+    }
+    
+    // Variants name a base type with "extends" and only list the fields they change,
+    // flatten them here so every lookup sees a complete entry
+    fn resolve_variants(data: Value) -> Value {
+        let entries = match data.as_object() {
+            Some(entries) => entries,
+            None => return data,
+        };
+        
+        let mut resolved = serde_json::Map::new();
+        for (name, info) in entries {
+            // The comments block documents the fields, its "extends" is a description
+            if name == "comments" {
+                resolved.insert(name.clone(), info.clone());
+                continue;
+            }
+            let mut chain = vec![info];
+            let mut current = info;
+            while let Some(base) = current["extends"].as_str() {
+                match entries.get(base) {
+                    Some(base_info) if chain.len() < Self::MAX_VARIANT_DEPTH => {
+                        chain.push(base_info);
+                        current = base_info;
+                    }
+                    Some(_) => {
+                        eprintln!("Enemy {} extends too deeply, is there a cycle?", name);
+                        break;
+                    }
+                    None => {
+                        eprintln!("Enemy {} extends unknown type {}", name, base);
+                        break;
+                    }
+                }
+            }
+            
+            // Apply the furthest base first so nearer entries override it
+            let mut merged = serde_json::Map::new();
+            for entry in chain.iter().rev() {
+                if let Some(fields) = entry.as_object() {
+                    for (key, value) in fields {
+                        merged.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            merged.remove("extends");
+            resolved.insert(name.clone(), Value::Object(merged));
+        }
+        Value::Object(resolved)
+    }
+    
+    // Re-reads the settings file. A half-edited file that fails to parse keeps the previous data
+    pub fn reload(&mut self) -> bool {
+        match Self::load_enemy_data(&self.json_path) {
+            Some(data) => {
+                self.enemy_data = data;
+                true
+            }
+            None => false,
+        }
+    }
+    
+    pub fn json_path(&self) -> &str {
+        &self.json_path
+    }
+    
+    pub fn spawn_enemy(
+        &self,
+        enemy_type: &str,
+        ecs_manager: &mut ECSManager,
+        renderer: &mut Renderer,
+        assets: &mut Assets,
+        x: i32,
+        y: i32,
+    ) -> Option<(EntityID, EnemyAnimator)> {
+        if let Some(enemy_info) = self.enemy_data.get(enemy_type) {
+            if let Some(animator) = self.create_animator(enemy_type, assets)
+                && let Some(sprite) = animator.animations.get_current_frame().cloned() {
+                // Spawned on the first frame of the starting state, the animator takes over from there
+                let entity_id = ecs_manager.create_entity(enemy_type, x, y, 1);
+                if let Some(entity) = ecs_manager.get_entity_mut(entity_id) {
+                    // Keep the collider over the art whatever the pivot is
+                    let (center_x, center_y) = sprite.get().center_offset();
+                    entity.set_collider(32, 32, center_x - 16, center_y - 16);
+                    entity.simple_collisions = true;
+                    entity.set_sprite(sprite.clone());
+                    entity.solid = true;
+                    entity.pushable = enemy_info["pushable"].as_bool().unwrap_or(true);
+                    entity.mass = enemy_info["mass"].as_f64().unwrap_or(1.0) as f32;
+                    
+                    // Y-sorted layers order enemies by the bottom of their collider
+                    let sort_offset_y = entity.collider.as_ref()
+                        .map(|c| c.offset_y + c.height as i32)
+                        .unwrap_or(0);
+                    
+                    renderer.add_sprite_instance(entity_id, crate::renderer::SpriteInstance {
+                        sprite,
+                        position_x: x,
+                        position_y: y,
+                        z_order: 1,
+                        flip_horizontal: false,
+                        layer: Layer::Entities,
+                        sort_offset_y,
+                        viewport: None,
+                    });
+                    
+                    // Torch-carrying enemies bring their own light into dark areas
+                    if let Some(light) = enemy_info.get("light") {
+                        let radius = light["radius"].as_f64().unwrap_or(120.0) as f32;
+                        let color = light["color"].as_str().and_then(parse_hex_color).unwrap_or(0xFFFFB060);
+                        let intensity = light["intensity"].as_f64().unwrap_or(1.0) as f32;
+                        renderer.lighting.add_light(
+                            PointLight::new(radius, color).attached(entity_id).with_intensity(intensity)
+                        );
+                    }
+                    
+                    println!("Spawned {} at ({}, {})", enemy_type, x, y);
+                    return Some((entity_id, animator));
+                }
+            }
+        }
+        
+        eprintln!("Failed to spawn enemy type: {}", enemy_type);
+        None
+    }
+    
+    // Source image of an enemy type with its scale, pivot and palette applied
+    fn sheet_key(enemy_info: &Value) -> Option<SpriteKey> {
+        let sprite_path = enemy_info["sprite"].as_str()?;
+        // Use a fallback sprite if the specified one doesn't exist
+        let actual_sprite_path = if std::path::Path::new(sprite_path).exists() {
+            sprite_path
+        } else {
+            "assets/sprites/missing_texture.png"
+        };
+        
+        let scale = enemy_info["scale"].as_u64().unwrap_or(1) as u32;
+        let mut key = SpriteKey::new(actual_sprite_path).scale(scale);
+        if let Some(pivot) = enemy_info["pivot"].as_str() {
+            key = key.named_pivot(pivot);
+        }
+        // Recolored variants reuse the base art, the swap is applied once when the sprite is cached
+        if let Some(palette) = PaletteSwap::from_json(&enemy_info["palette"]) {
+            key = key.palette(palette);
+        }
+        Some(key)
+    }
+    
+    // One clip per entry of the "states" block and a state machine to pick between them.
+    // "frame_size" cuts the sprite into a grid and each state lists the cells it plays in "frames",
+    // without it the whole image is the only frame
+    pub fn create_animator(&self, enemy_type: &str, assets: &mut Assets) -> Option<EnemyAnimator> {
+        let enemy_info = self.enemy_data.get(enemy_type)?;
+        let sheet = Self::sheet_key(enemy_info)?;
+        
+        let cells = match enemy_info["frame_size"].as_array() {
+            Some(size) if size.len() == 2 => {
+                let dimension = |value: &Value| value.as_u64().unwrap_or(0) as u32;
+                let layout = GridLayout::new(dimension(&size[0]), dimension(&size[1]));
+                AnimationHandler::load_sprite_grid(assets, &sheet, &layout, None)
+            }
+            _ => vec![assets.sprite(sheet.clone())],
+        };
+        if cells.is_empty() {
+            eprintln!("Sprite of {} is smaller than its frame_size", enemy_type);
+            return None;
+        }
+        
+        let mut animations = AnimationHandler::new();
+        let mut states = AnimationStateMachine::from_json(&enemy_info["states"], "idle")
+            .unwrap_or_else(|| AnimationStateMachine::new("idle"));
+        
+        match enemy_info["states"].as_object() {
+            Some(state_infos) => {
+                for (state, info) in state_infos {
+                    let animation = info["animation"].as_str().unwrap_or(state);
+                    // A list of cells, or {"down": [...], "up": [...], "right": [...]} for a directional set
+                    let clips: Vec<(String, &Value)> = match &info["frames"] {
+                        Value::Object(directions) => directions.iter()
+                            .filter(|(direction, _)| {
+                                let known = Direction::from_name(direction).is_some();
+                                if !known {
+                                    eprintln!("Unknown direction {} in {} {}", direction, enemy_type, state);
+                                }
+                                known
+                            })
+                            .map(|(direction, frames)| (format!("{}_{}", animation, direction), frames))
+                            .collect(),
+                        frames => vec![(animation.to_string(), frames)],
+                    };
+                    
+                    let duration = info["frame_duration"].as_f64().unwrap_or(Self::DEFAULT_FRAME_DURATION) as f32;
+                    let direction = info["direction"].as_str().and_then(LoopDirection::from_name).unwrap_or(LoopDirection::Forward);
+                    let repeat = if info["looped"].as_bool() == Some(false) { Some(1) } else { None };
+                    
+                    for (clip, frames) in clips {
+                        let indices: Vec<usize> = frames.as_array()
+                            .map(|frames| frames.iter().filter_map(|i| i.as_u64()).map(|i| i as usize).collect())
+                            .unwrap_or_else(|| vec![0]);
+                        let frames: Vec<_> = indices.iter()
+                            .filter_map(|&index| {
+                                let cell = cells.get(index).cloned();
+                                if cell.is_none() {
+                                    eprintln!("Frame {} of {} {} is past the last cell", index, enemy_type, state);
+                                }
+                                cell
+                            })
+                            .collect();
+                        animations.add_animation(&clip, frames.clone(), vec![duration; frames.len()], direction, repeat);
+                    }
+                }
+            }
+            None => {
+                // Types without states stand still on their first frame
+                animations.add_animation("idle", vec![cells[0].clone()], vec![Self::DEFAULT_FRAME_DURATION as f32], LoopDirection::Forward, None);
+                states.add_state("idle", AnimationState {
+                    animation: "idle".to_string(),
+                    speed: 1.0,
+                    looped: true,
+                    duration: None,
+                    return_to: None,
+                });
+            }
+        }
+        
+        for (animation, frame, event) in self.animation_events(enemy_type) {
+            if !animations.add_event(&animation, frame, &event) {
+                eprintln!("Event {} of {} is past the last frame of {}", event, enemy_type, animation);
+            }
+        }
+        
+        states.start(&mut animations);
+        Some(EnemyAnimator { animations, states })
+    }
+    
+    // (animation, frame, event) triples from the "states" block. "damage_frame" is shorthand
+    // for a "hit" event, "events" maps frame numbers to an event name or a list of names
+    pub fn animation_events(&self, enemy_type: &str) -> Vec<(String, usize, String)> {
+        let mut events = Vec::new();
+        let states = match self.enemy_data.get(enemy_type).and_then(|info| info["states"].as_object()) {
+            Some(states) => states,
+            None => return events,
+        };
+        
+        for (state, info) in states {
+            let animation = info["animation"].as_str().unwrap_or(state).to_string();
+            
+            if let Some(frame) = info["damage_frame"].as_u64() {
+                events.push((animation.clone(), frame as usize, "hit".to_string()));
+            }
+            
+            if let Some(frames) = info["events"].as_object() {
+                for (frame, names) in frames {
+                    let frame = match frame.parse::<usize>() {
+                        Ok(frame) => frame,
+                        Err(_) => {
+                            eprintln!("Event frame {} of {} {} is not a number", frame, enemy_type, state);
+                            continue;
+                        }
+                    };
+                    let names: Vec<&str> = match names {
+                        Value::String(name) => vec![name.as_str()],
+                        Value::Array(names) => names.iter().filter_map(|n| n.as_str()).collect(),
+                        _ => Vec::new(),
+                    };
+                    events.extend(names.into_iter().map(|name| (animation.clone(), frame, name.to_string())));
+                }
+            }
+        }
+        events
+    }
+    
+    pub fn get_enemy_stats(&self, enemy_type: &str) -> Option<EnemyStats> {
+        self.enemy_data.get(enemy_type).map(|enemy_info| {
+            EnemyStats {
+                health: enemy_info["health"].as_u64().unwrap_or(100) as i32,
+                max_health: enemy_info["max_health"].as_u64().unwrap_or(100) as i32,
+                speed: enemy_info["speed"].as_f64().unwrap_or(3.0) as f32,
+                damage: enemy_info["damage"].as_u64().unwrap_or(20) as i32,
+                experience: enemy_info["experience"].as_u64().unwrap_or(0) as i32,
+                aggro_range: enemy_info["aggro_range"].as_u64().unwrap_or(300) as i32,
+                attack_range: enemy_info["attack_range"].as_u64().unwrap_or(50) as i32,
+                attack_cooldown: enemy_info["attack_cooldown"].as_f64().unwrap_or(1.5) as f32,
+                knockback_resistance: enemy_info["knockback_resistance"].as_f64().unwrap_or(0.0) as f32,
+                ai_type: enemy_info["ai_type"].as_str().unwrap_or("patrol").to_string(),
+                pushable: enemy_info["pushable"].as_bool().unwrap_or(true),
+                mass: enemy_info["mass"].as_f64().unwrap_or(1.0) as f32,
+                scale: enemy_info["scale"].as_u64().unwrap_or(1) as u32,
+                attack_sequence: vec![20],
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnemyStats {
+    pub health: i32,
+    pub max_health: i32,
+    pub speed: f32,
+    pub damage: i32,
+    pub experience: i32,
+    pub aggro_range: i32,
+    pub attack_range: i32,
+    pub attack_cooldown: f32,
+    pub knockback_resistance: f32,
+    pub ai_type: String,
+    pub pushable: bool,
+    pub mass: f32,
+    pub scale: u32,
+    pub attack_sequence: Vec<i32>,
 }
\ No newline at end of file
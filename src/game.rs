@@ -1,221 +1,1013 @@
-use crate::ecs::{ECSManager, EntityID};
-use crate::player::Player;
-use crate::renderer::Renderer;
-use crate::enemy_manager::EnemyManager;
-use crate::camera::Camera;
-use std::time::{Duration, Instant};
-use rodio::{Decoder, OutputStream, Sink};
-use std::fs::File;
-use std::io::BufReader;
-
-pub struct Game {
-    pub player: Player,
-    pub ecs_manager: ECSManager,
-    enemy_manager: EnemyManager,
-    pub camera: Camera,
-    enemy_ids: Vec<EntityID>,
-    enemy_types: Vec<String>,
-    #[allow(unused)]
-    stream: OutputStream,
-    #[allow(unused)]
-    sink: Sink,
-    last_frame: Instant,
-    accumulated_time: f32,
-}
-
-impl Game {
-    const FPS: u64 = 60;
-    const FIXED_DELTA: f32 = 1.0 / 60.0;
-    
-    pub fn new(renderer: &mut Renderer, width: i32, height: i32) -> Self {
-        let mut ecs_manager = ECSManager::new(width, height);
-        let enemy_manager = EnemyManager::new("assets/settings/enemies.json");
-        
-        let (stream, stream_handle) = OutputStream::try_default().unwrap();
-        let sink = Sink::try_new(&stream_handle).unwrap();
-        
-        let test_sound = Decoder::new(BufReader::new(File::open("assets/audio/music/aphex.mp3").unwrap())).unwrap();
-        sink.append(test_sound);
-        
-        let player = Player::new(renderer);
-        ecs_manager.add_entity(player.entity.clone());
-        
-        let mut enemy_ids = Vec::new();
-        let mut enemy_types = Vec::new();
-        
-        let positions = [(400, 300), (500, 250), (600, 350)];
-        for &(x, y) in &positions {
-            if let Some(id) = enemy_manager.spawn_enemy("Bug", &mut ecs_manager, renderer, x, y) {
-                enemy_ids.push(id);
-                enemy_types.push("Bug".to_string());
-            }
-        }
-        
-        let mut camera = Camera::new(width, height);
-        camera.follow(player.entity.position_x, player.entity.position_y);
-        
-        let game = Game {
-            player,
-            ecs_manager,
-            enemy_manager,
-            camera,
-            enemy_ids,
-            enemy_types,
-            stream,
-            sink,
-            last_frame: Instant::now(),
-            accumulated_time: 0.0,
-        };
-        
-        renderer.set_camera(game.camera.x, game.camera.y);
-        game
-    }
-    
-    pub fn update(&mut self, renderer: &mut Renderer, delta: f32) {
-        self.accumulated_time += delta;
-        
-        // Fixed timestep for physics
-        while self.accumulated_time >= Self::FIXED_DELTA {
-            self.fixed_update(renderer);
-            self.accumulated_time -= Self::FIXED_DELTA;
-        }
-        
-        // Smooth camera follow
-        self.camera.follow_smooth(
-            self.player.entity.position_x,
-            self.player.entity.position_y,
-            0.1
-        );
-        renderer.set_camera(self.camera.x, self.camera.y);
-    }
-    
-    fn fixed_update(&mut self, renderer: &mut Renderer) {
-        // Update player first
-        self.player.update(renderer, &mut self.ecs_manager);
-        
-        let player_pos = (self.player.entity.position_x, self.player.entity.position_y);
-        
-        // Collect AI decisions first to avoid borrowing conflicts
-        let mut enemy_movements = Vec::new();
-        
-        for (i, &enemy_id) in self.enemy_ids.iter().enumerate() {
-            // Get enemy data without holding mutable reference for too long
-            let enemy_type = self.enemy_types[i].clone();
-            let stats = self.enemy_manager.get_enemy_stats(&enemy_type);
-            
-            // Get enemy position and ID
-            let (enemy_x, enemy_y, enemy_vx, enemy_vy) = {
-                if let Some(enemy) = self.ecs_manager.get_entity(enemy_id) {
-                    (enemy.position_x, enemy.position_y, enemy.velocity_x, enemy.velocity_y)
-                } else {
-                    continue;
-                }
-            };
-            
-            // Create a temporary enemy for AI calculations
-            let mut temp_enemy = crate::ecs::Entity::new(
-                enemy_id,
-                &enemy_type,
-                enemy_x,
-                enemy_y,
-                1
-            );
-            temp_enemy.velocity_x = enemy_vx;
-            temp_enemy.velocity_y = enemy_vy;
-            
-            // Create a temporary player entity for AI calculations
-            let temp_player = crate::ecs::Entity::new(
-                crate::ecs::EntityID(0),
-                "player",
-                player_pos.0,
-                player_pos.1,
-                0
-            );
-            
-            // Calculate AI velocity
-            //match self.enemy_manager.ai.update_enemy(
-               // &enemy_type,
-             //   enemy_id,
-           //     &mut temp_enemy,
-              //  &temp_player,
-             //   stats,
-           // ) {
-             //   Ok((ai_vx, ai_vy)) => {
-                    // Store movement for this enemy
-            //        enemy_movements.push((enemy_id, ai_vx * Self::FIXED_DELTA, ai_vy * Self::FIXED_DELTA));
-            //    }
-              //  Err(e) => {
-             //       eprintln!("AI error for enemy {}: {}", enemy_type, e);
-             //   }
-          //  }
-        }
-        
-        // Apply movements one at a time to prevent chain reactions
-        for (enemy_id, move_x, move_y) in enemy_movements {
-            // Temporarily mark other enemies as non-solid to prevent them from affecting each other
-            let mut original_solid_states = Vec::new();
-            
-            // Use the public iterator method instead of accessing private field
-            for other_id in self.ecs_manager.iter_entity_ids() {
-                if other_id != enemy_id && other_id != self.player.entity_id {
-                    if let Some(other_entity) = self.ecs_manager.get_entity(other_id) {
-                        if other_entity.solid {
-                            original_solid_states.push(other_id);
-                        }
-                    }
-                }
-            }
-            
-            // Temporarily make other enemies non-solid
-            for &id in &original_solid_states {
-                if let Some(entity) = self.ecs_manager.get_entity_mut(id) {
-                    entity.solid = false;
-                }
-            }
-            
-            // Get old position before moving
-            let old_position = self.ecs_manager.get_entity(enemy_id)
-                .map(|e| (e.position_x, e.position_y));
-            
-            // Apply movement (enemies won't affect each other now)
-            let moved = self.ecs_manager.move_entity(enemy_id, move_x, move_y);
-            
-            // Restore solid states
-            for &id in &original_solid_states {
-                if let Some(entity) = self.ecs_manager.get_entity_mut(id) {
-                    entity.solid = true;
-                }
-            }
-            
-            if moved {
-                // Update renderer if position changed
-                if let Some((old_x, old_y)) = old_position {
-                    if let Some(updated) = self.ecs_manager.get_entity(enemy_id) {
-                        if updated.position_x != old_x || updated.position_y != old_y {
-                            renderer.move_sprite(enemy_id, updated.position_x, updated.position_y);
-                        }
-                    }
-                }
-            }
-        }
-    }
-    
-    pub fn run(&mut self, renderer: &mut Renderer) {
-        let frame_duration = Duration::from_millis(1000 / Self::FPS);
-        
-        while renderer.is_open() {
-            let start = Instant::now();
-            
-            let delta = self.last_frame.elapsed().as_secs_f32();
-            self.last_frame = Instant::now();
-            
-            self.update(renderer, delta);
-            renderer.render_frame();
-            
-            let elapsed = start.elapsed();
-            if elapsed < frame_duration {
-                std::thread::sleep(frame_duration - elapsed);
-            }
-        }
-    }
+use crate::assets::Assets;
+use crate::ecs::{ECSManager, EntityID};
+use crate::player::{Controls, Player};
+use crate::renderer::{PickMode, Renderer, ResizeMode, Viewport};
+use crate::render_layers::Layer;
+use crate::enemy_manager::{EnemyAnimator, EnemyManager};
+use crate::animation_manager::Direction;
+use crate::camera::{Camera, CameraMove, Easing};
+use crate::particles::{EmitterAnchor, EmitterID, ParticleSystem};
+use crate::lighting::{Occluder, PointLight};
+use crate::debug_draw::{DebugCategory, DebugOverlay};
+use crate::hot_reload::{has_extension, FileWatcher};
+use crate::input::{Binding, Input};
+use crate::replay::Replay;
+use crate::helper_methods::load_json;
+use minifb::{Key, MouseButton};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use rodio::{Decoder, OutputStream, Sink};
+use std::fs::File;
+use std::io::BufReader;
+use std::collections::HashSet;
+use std::path::Path;
+
+pub struct Game {
+    pub player: Player,
+    // Second local player on the arrow keys, the screen is split between the two while joined
+    pub player_two: Option<Player>,
+    pub ecs_manager: ECSManager,
+    enemy_manager: EnemyManager,
+    pub camera: Camera,
+    pub input: Input,
+    player_two_camera: Camera,
+    // Zoomed out picture-in-picture view around player one
+    minimap: Option<Camera>,
+    pub particles: ParticleSystem,
+    pub assets: Assets,
+    asset_watcher: FileWatcher,
+    dust_emitter: Option<EmitterID>,
+    enemy_ids: Vec<EntityID>,
+    enemy_types: Vec<String>,
+    enemy_animators: Vec<EnemyAnimator>,
+    // Seconds until each enemy may start another attack
+    enemy_cooldowns: Vec<f32>,
+    // Starts from the type's stats, at zero or below the enemy is dead
+    enemy_health: Vec<i32>,
+    // Entity clicked on while the debug overlay is open
+    selected_entity: Option<EntityID>,
+    mouse_was_down: bool,
+    // Which of player one's actions is waiting for a new key
+    rebinding: Option<usize>,
+    // Every tick's input while recording, see Replay
+    pub recording: Option<Replay>,
+    // None when there is no audio device or the music failed to load
+    #[allow(unused)]
+    music: Option<(OutputStream, Sink)>,
+    last_frame: Instant,
+    accumulated_time: f32,
+}
+
+impl Game {
+    const FPS: u64 = 60;
+    const FIXED_DELTA: f32 = 1.0 / 60.0;
+    const DUNGEON_AMBIENT: f32 = 0.15;
+    const CAPTURE_DUMP_EVERY: u32 = 4;
+    const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+    const ATLAS_MANIFEST: &str = "assets/atlas/sprites.json";
+    const CAMERA_SETTINGS: &str = "assets/settings/camera.json";
+    const INPUT_SETTINGS: &str = "assets/settings/input.json";
+    const REBIND_KEY: Key = Key::Insert;
+    const MUSIC: &str = "assets/audio/music/aphex.mp3";
+    // Camera trauma added when an enemy's attack lands
+    const HIT_TRAUMA: f32 = 0.4;
+    // How far from the player a swing reaches and what it takes off
+    const ATTACK_REACH: f32 = 60.0;
+    const ATTACK_DAMAGE: i32 = 40;
+    // Player two joins this far to the right of player one
+    const PLAYER_TWO_OFFSET: i32 = 40;
+    const SPLIT_BORDER: u32 = 0xFF202020;
+    const MINIMAP_SIZE: (usize, usize) = (160, 120);
+    const MINIMAP_MARGIN: usize = 8;
+    const MINIMAP_ZOOM: f32 = 0.2;
+    const MINIMAP_BORDER: u32 = 0xFFC0C0C0;
+    // Toggle the render layers in Layer::ALL order
+    const LAYER_KEYS: [Key; 5] = [Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5];
+    
+    // `seed` drives all randomness, the same seed and input replay the same game
+    pub fn new(renderer: &mut Renderer, width: i32, height: i32, seed: u32) -> Self {
+        let mut ecs_manager = ECSManager::new(width, height);
+        let mut assets = Assets::new();
+        // Built by `cargo run --bin pack_atlas`, loose files are used while it does not exist
+        if Path::new(Self::ATLAS_MANIFEST).exists() {
+            let packed = assets.load_atlas(Self::ATLAS_MANIFEST);
+            println!("Loaded {} atlas sprites from {}", packed, Self::ATLAS_MANIFEST);
+        }
+        renderer.load_layers("assets/settings/render_layers.json", &mut assets);
+        let enemy_manager = EnemyManager::new("assets/settings/enemies.json");
+        
+        // Headless runs stay quiet
+        let music = match renderer.window {
+            Some(_) => Self::start_music(Self::MUSIC),
+            None => None,
+        };
+        
+        let player = Player::new(renderer, &mut assets, EntityID(0), 100, 100, Controls::PLAYER_ONE);
+        ecs_manager.add_entity(player.entity.clone());
+        
+        let mut particles = ParticleSystem::new("assets/settings/particles.json", seed, &mut assets);
+        
+        // Dust kicks up from the bottom of the player's collider while walking
+        let feet_offset = player.entity.collider.as_ref()
+            .map(|c| (c.offset_y + c.height as i32) as f32)
+            .unwrap_or(0.0);
+        let dust_emitter = particles.spawn_emitter("dust", EmitterAnchor::Entity {
+            id: player.entity_id,
+            offset_x: 0.0,
+            offset_y: feet_offset,
+        });
+        if let Some(id) = dust_emitter {
+            particles.set_active(id, false);
+        }
+        
+        renderer.lighting.ambient = Self::DUNGEON_AMBIENT;
+        renderer.lighting.add_light(PointLight::new(220.0, 0xFFFFE8C0).attached(player.entity_id));
+        
+        let mut enemy_ids = Vec::new();
+        let mut enemy_types = Vec::new();
+        let mut enemy_animators = Vec::new();
+        
+        let spawns = [("Bug", 400, 300), ("Bug", 500, 250), ("Bug", 600, 350), ("EliteBug", 520, 420)];
+        for &(enemy_type, x, y) in &spawns {
+            if let Some((id, animator)) = enemy_manager.spawn_enemy(enemy_type, &mut ecs_manager, renderer, &mut assets, x, y) {
+                enemy_ids.push(id);
+                enemy_types.push(enemy_type.to_string());
+                enemy_animators.push(animator);
+            }
+        }
+        
+        let enemy_health = enemy_types.iter()
+            .map(|enemy_type| enemy_manager.get_enemy_stats(enemy_type).map(|stats| stats.health).unwrap_or(1))
+            .collect();
+        
+        let mut camera = Camera::new(width, height);
+        camera.load_settings(Self::CAMERA_SETTINGS);
+        camera.follow(player.entity.position_x, player.entity.position_y);
+        
+        // Tour the bugs and settle on the elite like a boss room would, then hand the camera back
+        // to the player. Timings and easing come from the "intro" block of the camera settings
+        if let Some(index) = enemy_types.iter().position(|enemy_type| enemy_type == "EliteBug")
+            && let Some(elite) = ecs_manager.get_entity(enemy_ids[index]) {
+            let intro = &load_json(Self::CAMERA_SETTINGS)["intro"];
+            let step = |name: &str| (
+                intro[name][0].as_f64().unwrap_or(1.0) as f32,
+                intro[name][1].as_str().and_then(Easing::from_name).unwrap_or(Easing::EaseInOut),
+            );
+            
+            let tour: Vec<(f32, f32)> = enemy_ids.iter()
+                .filter(|&&id| id != elite.id)
+                .filter_map(|&id| ecs_manager.get_entity(id))
+                .map(|bug| (bug.position_x as f32, bug.position_y as f32))
+                .collect();
+            if !tour.is_empty() {
+                let (duration, easing) = step("tour");
+                camera.queue_move(CameraMove::FollowPath { points: tour, duration, easing });
+            }
+            let (duration, easing) = step("settle");
+            camera.queue_move(CameraMove::PanTo { x: elite.position_x as f32, y: elite.position_y as f32, duration, easing });
+            camera.queue_move(CameraMove::Hold { duration: intro["hold"].as_f64().unwrap_or(0.8) as f32 });
+            let (duration, easing) = step("back");
+            let (player_x, player_y) = (player.entity.position_x as f32, player.entity.position_y as f32);
+            camera.queue_move(CameraMove::PanTo { x: player_x, y: player_y, duration, easing });
+        }
+        
+        let mut input = Input::new();
+        input.load_bindings(Self::INPUT_SETTINGS);
+        
+        let mut player_two_camera = Camera::new(width, height);
+        player_two_camera.load_settings(Self::CAMERA_SETTINGS);
+        
+        let mut game = Game {
+            player,
+            player_two: None,
+            ecs_manager,
+            enemy_manager,
+            camera,
+            input,
+            player_two_camera,
+            minimap: None,
+            particles,
+            assets,
+            asset_watcher: FileWatcher::new("assets", Self::HOT_RELOAD_INTERVAL),
+            dust_emitter,
+            enemy_ids,
+            enemy_cooldowns: vec![0.0; enemy_types.len()],
+            enemy_health,
+            enemy_types,
+            enemy_animators,
+            selected_entity: None,
+            mouse_was_down: false,
+            rebinding: None,
+            recording: None,
+            music,
+            last_frame: Instant::now(),
+            accumulated_time: 0.0,
+        };
+        
+        game.layout_viewports(renderer);
+        game
+    }
+    
+    pub fn update(&mut self, renderer: &mut Renderer, delta: f32) {
+        self.accumulated_time += delta;
+        
+        // Fixed timestep for physics
+        while self.accumulated_time >= Self::FIXED_DELTA {
+            // Once per tick, so pressed and released line up with the simulation
+            if let Some(window) = &renderer.window {
+                self.input.update(window);
+            }
+            self.fixed_update(renderer);
+            self.accumulated_time -= Self::FIXED_DELTA;
+        }
+        
+        if renderer.is_key_pressed(Key::F9) {
+            renderer.resize_mode = match renderer.resize_mode {
+                ResizeMode::IntegerScaled => ResizeMode::Expand,
+                ResizeMode::Expand => ResizeMode::IntegerScaled,
+            };
+        }
+        
+        renderer.handle_resize();
+        
+        if renderer.is_key_pressed(Key::F8) {
+            self.cycle_input_layout();
+        }
+        self.handle_rebinding(renderer);
+        
+        if renderer.is_key_pressed(Key::Equal) {
+            self.camera.step_zoom(1);
+        }
+        if renderer.is_key_pressed(Key::Minus) {
+            self.camera.step_zoom(-1);
+        }
+        
+        self.update_cameras(renderer, delta);
+        
+        if renderer.is_key_pressed(Key::L) {
+            renderer.lighting.enabled = !renderer.lighting.enabled;
+        }
+        Self::handle_layer_keys(renderer);
+        
+        self.handle_capture_keys(renderer);
+        if let Some(window) = &renderer.window {
+            renderer.debug.handle_keys(window);
+        }
+        self.handle_debug_picking(renderer);
+        self.hot_reload();
+        
+        if renderer.lighting.enabled {
+            renderer.lighting.set_occluders(
+                self.ecs_manager.iter_entities()
+                    .filter(|e| e.solid)
+                    .filter_map(|e| e.get_collider_bounds().map(|(left, top, right, bottom)| Occluder {
+                        owner: Some(e.id),
+                        left,
+                        top,
+                        right,
+                        bottom,
+                    }))
+            );
+        }
+        
+        self.particles.submit(renderer);
+    }
+    
+    // Plays a recorded session back one tick at a time, no window needed. The cameras
+    // advance at the tick rate so the last frame can be compared against a golden image.
+    // Returns the world checksum at the end
+    pub fn play_replay(&mut self, renderer: &mut Renderer, replay: &Replay) -> u64 {
+        for tick in 0..replay.tick_count() {
+            self.input.set_held(replay.held_on(tick));
+            self.fixed_update(renderer);
+            self.update_cameras(renderer, Self::FIXED_DELTA);
+        }
+        self.particles.submit(renderer);
+        self.world_checksum()
+    }
+    
+    // FNV-1a over every entity's id and position in id order. Two runs that end on the
+    // same checksum ended in the same world state
+    pub fn world_checksum(&self) -> u64 {
+        let mut entities: Vec<_> = self.ecs_manager.iter_entities()
+            .map(|e| (e.id.0 as u64, e.position_x as u64, e.position_y as u64))
+            .collect();
+        entities.sort();
+        
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for (id, x, y) in entities {
+            for value in [id, x, y] {
+                for byte in value.to_le_bytes() {
+                    hash ^= byte as u64;
+                    hash = hash.wrapping_mul(0x100000001b3);
+                }
+            }
+        }
+        hash
+    }
+    
+    fn update_cameras(&mut self, renderer: &mut Renderer, delta: f32) {
+        self.camera.update(delta);
+        
+        // Smooth camera follow, entity velocity is per tick and the camera wants it per second
+        self.camera.follow_smooth(
+            self.player.entity.position_x as f32,
+            self.player.entity.position_y as f32,
+            self.player.entity.velocity_x / Self::FIXED_DELTA,
+            self.player.entity.velocity_y / Self::FIXED_DELTA,
+            delta,
+        );
+        if let Some(player_two) = &self.player_two {
+            self.player_two_camera.update(delta);
+            self.player_two_camera.follow_smooth(
+                player_two.entity.position_x as f32,
+                player_two.entity.position_y as f32,
+                player_two.entity.velocity_x / Self::FIXED_DELTA,
+                player_two.entity.velocity_y / Self::FIXED_DELTA,
+                delta,
+            );
+        }
+        if let Some(minimap) = &mut self.minimap {
+            minimap.follow(self.player.entity.position_x, self.player.entity.position_y);
+        }
+        self.layout_viewports(renderer);
+    }
+    
+    // Side by side halves while player two is in, one full view otherwise, with the minimap
+    // in the top-right corner on top. Camera sizes follow their views so they stay centred
+    fn layout_viewports(&mut self, renderer: &mut Renderer) {
+        let (width, height) = (renderer.width, renderer.height);
+        let main_width = if self.player_two.is_some() { width / 2 } else { width };
+        
+        self.camera.viewport_width = main_width as i32;
+        self.camera.viewport_height = height as i32;
+        let mut viewports = vec![Viewport::new(0, 0, main_width, height).with_camera(&self.camera)];
+        
+        if self.player_two.is_some() {
+            self.player_two_camera.viewport_width = (width - main_width) as i32;
+            self.player_two_camera.viewport_height = height as i32;
+            viewports[0].border = Some(Self::SPLIT_BORDER);
+            viewports.push(
+                Viewport::new(main_width, 0, width - main_width, height)
+                    .with_camera(&self.player_two_camera)
+                    .with_border(Self::SPLIT_BORDER)
+            );
+        }
+        
+        if let Some(minimap) = &self.minimap {
+            let (minimap_width, minimap_height) = Self::MINIMAP_SIZE;
+            let x = width.saturating_sub(minimap_width + Self::MINIMAP_MARGIN);
+            let mut viewport = Viewport::new(x, Self::MINIMAP_MARGIN, minimap_width, minimap_height)
+                .with_camera(minimap)
+                .with_border(Self::MINIMAP_BORDER);
+            viewport.show_ui = false;
+            viewport.lighting = false;
+            viewports.push(viewport);
+        }
+        
+        renderer.viewports = viewports;
+    }
+    
+    fn toggle_player_two(&mut self, renderer: &mut Renderer) {
+        if let Some(player_two) = self.player_two.take() {
+            self.ecs_manager.remove_entity(player_two.entity_id);
+            renderer.remove_sprite_instance(player_two.entity_id);
+            renderer.lighting.remove_lights_attached_to(player_two.entity_id);
+            return;
+        }
+        
+        let x = self.player.entity.position_x + Self::PLAYER_TWO_OFFSET;
+        let y = self.player.entity.position_y;
+        let entity_id = self.ecs_manager.create_entity("player", x, y, 2);
+        let player_two = Player::new(renderer, &mut self.assets, entity_id, x, y, Controls::PLAYER_TWO);
+        self.ecs_manager.add_entity(player_two.entity.clone());
+        renderer.lighting.add_light(PointLight::new(220.0, 0xFFC0E8FF).attached(entity_id));
+        
+        // Start from player one's view so the split does not jump
+        self.player_two_camera.x = self.camera.x;
+        self.player_two_camera.y = self.camera.y;
+        self.player_two_camera.zoom = self.camera.zoom;
+        self.player_two_camera.zoom_target = self.camera.zoom_target;
+        self.player_two = Some(player_two);
+    }
+    
+    // Shown while the map action is held
+    fn show_minimap(&mut self) {
+        let (minimap_width, minimap_height) = Self::MINIMAP_SIZE;
+        let mut minimap = Camera::new(minimap_width as i32, minimap_height as i32);
+        minimap.zoom = Self::MINIMAP_ZOOM;
+        minimap.zoom_target = Self::MINIMAP_ZOOM;
+        minimap.follow(self.player.entity.position_x, self.player.entity.position_y);
+        self.minimap = Some(minimap);
+    }
+    
+    fn start_music(path: &str) -> Option<(OutputStream, Sink)> {
+        let (stream, stream_handle) = match OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("No audio output, music is off: {}", e);
+                return None;
+            }
+        };
+        let sink = match Sink::try_new(&stream_handle) {
+            Ok(sink) => sink,
+            Err(e) => {
+                eprintln!("Failed to create audio sink: {}", e);
+                return None;
+            }
+        };
+        
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Failed to open music {}: {}", path, e);
+                return None;
+            }
+        };
+        match Decoder::new(BufReader::new(file)) {
+            Ok(source) => sink.append(source),
+            Err(e) => {
+                eprintln!("Failed to decode music {}: {}", path, e);
+                return None;
+            }
+        }
+        Some((stream, sink))
+    }
+    
+    // Steps to the next layout in input.json and saves it as the one to start with
+    fn cycle_input_layout(&mut self) {
+        let names = self.input.layout_names();
+        if names.is_empty() {
+            return;
+        }
+        let next = match self.input.layout().and_then(|current| names.iter().position(|&name| name == current)) {
+            Some(index) => names[(index + 1) % names.len()],
+            None => names[0],
+        }.to_string();
+        
+        self.input.set_layout(&next);
+        println!("Input layout {}", next);
+        self.save_input_settings();
+    }
+    
+    // Insert asks for a new key for each of player one's actions in turn, Escape keeps the
+    // current one. Keys are replaced, mouse buttons bound to the action stay
+    fn handle_rebinding(&mut self, renderer: &Renderer) {
+        let actions = Controls::PLAYER_ONE.actions();
+        let index = match self.rebinding {
+            Some(index) => index,
+            None => {
+                if renderer.is_key_pressed(Self::REBIND_KEY) {
+                    self.rebinding = Some(0);
+                    self.prompt_rebinding(actions[0]);
+                }
+                return;
+            }
+        };
+        let key = match renderer.keys_pressed().first() {
+            Some(&key) => key,
+            None => return,
+        };
+        
+        let action = actions[index];
+        if key != Key::Escape {
+            let mouse_buttons: Vec<Binding> = self.input.bindings(action).iter()
+                .filter(|binding| matches!(binding, Binding::Mouse(_)))
+                .copied()
+                .collect();
+            self.input.set_bindings(action, mouse_buttons);
+            self.input.bind(action, Binding::Key(key));
+        }
+        
+        match actions.get(index + 1) {
+            Some(next) => {
+                self.rebinding = Some(index + 1);
+                self.prompt_rebinding(next);
+            }
+            None => {
+                self.rebinding = None;
+                if self.save_input_settings() {
+                    println!("Controls saved to {}", Self::INPUT_SETTINGS);
+                }
+            }
+        }
+    }
+    
+    fn prompt_rebinding(&self, action: &str) {
+        let current: Vec<String> = self.input.bindings(action).iter().map(|binding| binding.name()).collect();
+        println!("Press a key for {} (now {}), Escape keeps it", action, current.join(", "));
+    }
+    
+    fn save_input_settings(&self) -> bool {
+        match self.input.save_bindings(Self::INPUT_SETTINGS) {
+            Ok(_) => true,
+            Err(e) => {
+                eprintln!("Failed to save {}: {}", Self::INPUT_SETTINGS, e);
+                false
+            }
+        }
+    }
+    
+    // With the overlay open, clicking selects the entity under the cursor and prints what it is
+    fn handle_debug_picking(&mut self, renderer: &Renderer) {
+        let down = renderer.is_mouse_down(MouseButton::Left);
+        let clicked = down && !self.mouse_was_down;
+        self.mouse_was_down = down;
+        if !clicked || !renderer.debug.any_enabled() {
+            return;
+        }
+        
+        let (screen_x, screen_y) = match renderer.mouse_screen_position() {
+            Some(position) => position,
+            None => return,
+        };
+        
+        // Exact pixels first, colliders catch sprites too small or thin to hit
+        let ecs_manager = &self.ecs_manager;
+        self.selected_entity = renderer.pick(screen_x, screen_y, PickMode::SpriteAlpha, |_| None)
+            .or_else(|| renderer.pick(screen_x, screen_y, PickMode::Collider, |id| {
+                ecs_manager.get_entity(id).and_then(|entity| entity.get_collider_bounds())
+            }));
+        
+        match self.selected_entity.and_then(|id| ecs_manager.get_entity(id)) {
+            Some(entity) => {
+                let (kind, state) = match self.enemy_ids.iter().position(|&id| id == entity.id) {
+                    Some(i) => (self.enemy_types[i].as_str(), self.enemy_animators[i].states.current_state()),
+                    None => match &self.player_two {
+                        Some(player_two) if player_two.entity_id == entity.id => ("player", player_two.states.current_state()),
+                        _ => ("player", self.player.states.current_state()),
+                    },
+                };
+                println!("Picked #{} ({}, {}) at ({}, {})", entity.id.0, kind, state, entity.position_x, entity.position_y);
+            }
+            None => {
+                if let Some((world_x, world_y)) = renderer.screen_to_world(screen_x, screen_y) {
+                    println!("Nothing at ({:.0}, {:.0})", world_x, world_y);
+                }
+            }
+        }
+    }
+    
+    fn hot_reload(&mut self) {
+        for path in self.asset_watcher.poll() {
+            if has_extension(&path, "png") {
+                if self.assets.reload(&path) {
+                    println!("Reloaded sprite {}", path.display());
+                }
+            } else if path == Path::new(self.enemy_manager.json_path()) {
+                if self.enemy_manager.reload() {
+                    // Stats are looked up every tick, only values copied onto live entities need pushing
+                    for (i, &enemy_id) in self.enemy_ids.iter().enumerate() {
+                        let stats = self.enemy_manager.get_enemy_stats(&self.enemy_types[i]);
+                        if let (Some(stats), Some(enemy)) = (stats, self.ecs_manager.get_entity_mut(enemy_id)) {
+                            enemy.mass = stats.mass;
+                            enemy.pushable = stats.pushable;
+                        }
+                        // New clips and transitions start over from the initial state
+                        if let Some(mut animator) = self.enemy_manager.create_animator(&self.enemy_types[i], &mut self.assets) {
+                            // The dead stay dead
+                            animator.states.set_bool("is_dead", self.enemy_health[i] <= 0);
+                            self.enemy_animators[i] = animator;
+                        }
+                    }
+                    println!("Reloaded {}", path.display());
+                }
+            } else if path == Path::new(Self::INPUT_SETTINGS) {
+                if self.input.load_bindings(Self::INPUT_SETTINGS) {
+                    println!("Reloaded {}", path.display());
+                }
+            } else if path == Path::new(Self::CAMERA_SETTINGS) {
+                let reloaded = self.camera.load_settings(Self::CAMERA_SETTINGS);
+                self.player_two_camera.load_settings(Self::CAMERA_SETTINGS);
+                if reloaded {
+                    println!("Reloaded {}", path.display());
+                }
+            } else if has_extension(&path, "lua") {
+                match self.enemy_manager.ai.reload_script(&path) {
+                    Ok(_) => println!("Reloaded script {}", path.display()),
+                    Err(e) => eprintln!("Failed to reload script {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+    
+    // 1-5 hide or show one render layer, 0 shows them all again
+    fn handle_layer_keys(renderer: &mut Renderer) {
+        for (layer, key) in Layer::ALL.into_iter().zip(Self::LAYER_KEYS) {
+            if renderer.is_key_pressed(key) {
+                let settings = renderer.layer_settings_mut(layer);
+                settings.visible = !settings.visible;
+                println!("Layer {} {}", layer.name(), if settings.visible { "shown" } else { "hidden" });
+            }
+        }
+        if renderer.is_key_pressed(Key::Key0) {
+            for layer in Layer::ALL {
+                renderer.set_layer_visible(layer, true);
+            }
+        }
+    }
+    
+    fn handle_capture_keys(&mut self, renderer: &mut Renderer) {
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let output_dir = renderer.capture.output_dir.clone();
+        
+        if renderer.is_key_pressed(Key::F12) {
+            let path = format!("{}/screenshot_{}.png", output_dir, stamp);
+            match std::fs::create_dir_all(&output_dir).map_err(image::ImageError::IoError)
+                .and_then(|_| renderer.save_screenshot(&path)) {
+                Ok(_) => println!("Saved screenshot to {}", path),
+                Err(e) => eprintln!("Failed to save screenshot {}: {}", path, e),
+            }
+        }
+        
+        if renderer.is_key_pressed(Key::F11) {
+            match std::fs::create_dir_all(&output_dir) {
+                Ok(_) => renderer.capture.save_clip(&format!("{}/clip_{}.gif", output_dir, stamp)),
+                Err(e) => eprintln!("Failed to create {}: {}", output_dir, e),
+            }
+        }
+        
+        if renderer.is_key_pressed(Key::F10) {
+            renderer.capture.dump_every = match renderer.capture.dump_every {
+                Some(_) => None,
+                None => Some(Self::CAPTURE_DUMP_EVERY),
+            };
+        }
+    }
+    
+    fn fixed_update(&mut self, renderer: &mut Renderer) {
+        renderer.debug.clear();
+        
+        if let Some(recording) = &mut self.recording {
+            recording.record_tick(&self.input);
+        }
+        
+        // Actions rather than hotkeys so joining and the map are part of recorded input
+        if self.input.pressed("p2_join") {
+            self.toggle_player_two(renderer);
+        }
+        if self.input.pressed("map") {
+            self.show_minimap();
+        }
+        if self.input.released("map") {
+            self.minimap = None;
+        }
+        
+        // Update player first
+        self.player.update(&self.input, renderer, &mut self.ecs_manager, Self::FIXED_DELTA);
+        
+        if let Some(id) = self.dust_emitter {
+            let walking = self.player.input_dx != 0.0 || self.player.input_dy != 0.0;
+            self.particles.set_active(id, walking);
+        }
+        
+        // Swings that landed this tick, resolved once both players have moved
+        let mut strikes = Vec::new();
+        for event in self.player.animations.drain_events() {
+            // The player's position is at their feet
+            let position = (self.player.entity.position_x, self.player.entity.position_y);
+            match event.as_str() {
+                "footstep" => {
+                    self.particles.burst_at("footstep", position.0, position.1);
+                }
+                "hit" => strikes.push(position),
+                _ => {}
+            }
+        }
+        
+        if let Some(player_two) = &mut self.player_two {
+            player_two.update(&self.input, renderer, &mut self.ecs_manager, Self::FIXED_DELTA);
+            for event in player_two.animations.drain_events() {
+                let position = (player_two.entity.position_x, player_two.entity.position_y);
+                match event.as_str() {
+                    "footstep" => {
+                    self.particles.burst_at("footstep", position.0, position.1);
+                }
+                    "hit" => strikes.push(position),
+                    _ => {}
+                }
+            }
+        }
+        
+        for (x, y) in strikes {
+            self.strike_enemies(x, y);
+        }
+        
+        let player_pos = (self.player.entity.position_x, self.player.entity.position_y);
+        
+        // Collect AI decisions first to avoid borrowing conflicts
+        let mut enemy_movements = Vec::new();
+        
+        for (i, &enemy_id) in self.enemy_ids.iter().enumerate() {
+            // Get enemy data without holding mutable reference for too long
+            let enemy_type = self.enemy_types[i].clone();
+            let stats = self.enemy_manager.get_enemy_stats(&enemy_type);
+            
+            // Get enemy position and ID
+            let (enemy_x, enemy_y, enemy_vx, enemy_vy) = {
+                if let Some(enemy) = self.ecs_manager.get_entity(enemy_id) {
+                    (enemy.position_x, enemy.position_y, enemy.velocity_x, enemy.velocity_y)
+                } else {
+                    continue;
+                }
+            };
+            
+            // Events crossed since the last tick, e.g. "hit" on the attack's damage frame
+            let events = self.enemy_animators[i].animations.drain_events();
+            for event in &events {
+                match event.as_str() {
+                    "footstep" => {
+                        self.particles.burst_at("footstep", enemy_x, enemy_y);
+                    }
+                    "hit" => {
+                        self.camera.add_trauma(Self::HIT_TRAUMA);
+                        // Sparks fly where the swing lands, the middle of the player's body
+                        let (spark_x, spark_y) = self.player.entity.get_collider_bounds()
+                            .map(|(left, top, right, bottom)| ((left + right) / 2, (top + bottom) / 2))
+                            .unwrap_or(player_pos);
+                        self.particles.burst_at("hit_sparks", spark_x, spark_y);
+                        if let Some(stats) = &stats
+                            && self.player.take_damage(stats.damage) {
+                            self.particles.burst_at("death_burst", player_pos.0, player_pos.1);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            
+            // Corpses finish their death animation but no longer think
+            if self.enemy_health[i] <= 0 {
+                continue;
+            }
+            
+            // Create a temporary enemy for AI calculations
+            let mut temp_enemy = crate::ecs::Entity::new(
+                enemy_id,
+                &enemy_type,
+                enemy_x,
+                enemy_y,
+                1
+            );
+            temp_enemy.velocity_x = enemy_vx;
+            temp_enemy.velocity_y = enemy_vy;
+            
+            // Create a temporary player entity for AI calculations
+            let temp_player = crate::ecs::Entity::new(
+                crate::ecs::EntityID(0),
+                "player",
+                player_pos.0,
+                player_pos.1,
+                0
+            );
+            
+            // Scripts are looked up by ai_type so variants share their base type's script
+            let ai_type = match &stats {
+                Some(stats) => stats.ai_type.clone(),
+                None => continue,
+            };
+            
+            // Calculate AI velocity
+            match self.enemy_manager.ai.update_enemy(
+                &ai_type,
+                enemy_id,
+                &mut temp_enemy,
+                &temp_player,
+                stats,
+                &events,
+                Self::FIXED_DELTA,
+            ) {
+                Ok((ai_vx, ai_vy)) => {
+                    // Store movement for this enemy, scripts work in pixels per tick like the player
+                    enemy_movements.push((enemy_id, ai_vx, ai_vy));
+                }
+                Err(e) => {
+                    eprintln!("AI error for enemy {}: {}", enemy_type, e);
+                }
+            }
+        }
+        
+        // Moves, shakes and zooms the scripts asked for during their update
+        for command in self.enemy_manager.ai.drain_camera_commands() {
+            self.camera.apply(command);
+        }
+        
+        // Apply movements one at a time to prevent chain reactions
+        for &(enemy_id, move_x, move_y) in &enemy_movements {
+            // Temporarily mark other enemies as non-solid to prevent them from affecting each other
+            let mut original_solid_states = Vec::new();
+            
+            // Use the public iterator method instead of accessing private field
+            for other_id in self.ecs_manager.iter_entity_ids() {
+                if other_id != enemy_id && other_id != self.player.entity_id
+                    && self.player_two.as_ref().is_none_or(|p| p.entity_id != other_id) {
+                    if let Some(other_entity) = self.ecs_manager.get_entity(other_id) {
+                        if other_entity.solid {
+                            original_solid_states.push(other_id);
+                        }
+                    }
+                }
+            }
+            
+            // Temporarily make other enemies non-solid
+            for &id in &original_solid_states {
+                if let Some(entity) = self.ecs_manager.get_entity_mut(id) {
+                    entity.solid = false;
+                }
+            }
+            
+            // Get old position before moving
+            let old_position = self.ecs_manager.get_entity(enemy_id)
+                .map(|e| (e.position_x, e.position_y));
+            
+            // Apply movement (enemies won't affect each other now)
+            let moved = self.ecs_manager.move_entity(enemy_id, move_x, move_y);
+            
+            // Restore solid states
+            for &id in &original_solid_states {
+                if let Some(entity) = self.ecs_manager.get_entity_mut(id) {
+                    entity.solid = true;
+                }
+            }
+            
+            if moved {
+                // Update renderer if position changed
+                if let Some((old_x, old_y)) = old_position {
+                    if let Some(updated) = self.ecs_manager.get_entity(enemy_id) {
+                        if updated.position_x != old_x || updated.position_y != old_y {
+                            renderer.move_sprite(enemy_id, updated.position_x, updated.position_y);
+                        }
+                    }
+                }
+            }
+        }
+        
+        self.update_enemy_animations(renderer, &enemy_movements, player_pos);
+        
+        self.particles.update(&self.ecs_manager, Self::FIXED_DELTA);
+        
+        self.queue_debug_shapes(&mut renderer.debug);
+    }
+    
+    // Combat and movement set the animation parameters, then every animator advances one tick
+    // Damages every living enemy within reach of a swing from (x, y)
+    fn strike_enemies(&mut self, x: i32, y: i32) {
+        for (i, &enemy_id) in self.enemy_ids.iter().enumerate() {
+            if self.enemy_health[i] <= 0 {
+                continue;
+            }
+            let Some(enemy) = self.ecs_manager.get_entity_mut(enemy_id) else {
+                continue;
+            };
+            let dx = (enemy.position_x - x) as f32;
+            let dy = (enemy.position_y - y) as f32;
+            if (dx * dx + dy * dy).sqrt() > Self::ATTACK_REACH {
+                continue;
+            }
+            
+            self.enemy_health[i] -= Self::ATTACK_DAMAGE;
+            let states = &mut self.enemy_animators[i].states;
+            if self.enemy_health[i] <= 0 {
+                states.set_bool("is_dead", true);
+                // Corpses stay where they fell without blocking anyone
+                enemy.solid = false;
+                self.particles.burst_at("death_burst", enemy.position_x, enemy.position_y);
+            } else {
+                states.set_trigger("is_hurt");
+                self.particles.burst_at("hit_sparks", enemy.position_x, enemy.position_y);
+            }
+        }
+    }
+    
+    fn update_enemy_animations(&mut self, renderer: &mut Renderer, enemy_moves: &[(EntityID, f32, f32)], player_pos: (i32, i32)) {
+        for (i, &enemy_id) in self.enemy_ids.iter().enumerate() {
+            let (move_x, move_y) = enemy_moves.iter()
+                .find(|(id, _, _)| *id == enemy_id)
+                .map(|&(_, x, y)| (x, y))
+                .unwrap_or((0.0, 0.0));
+            let animator = &mut self.enemy_animators[i];
+            // Standing still keeps facing the last way moved
+            if let Some(direction) = Direction::from_vector(move_x, move_y, true) {
+                animator.animations.set_facing(direction);
+            }
+            animator.states.set_float("speed", (move_x * move_x + move_y * move_y).sqrt());
+            
+            self.enemy_cooldowns[i] = (self.enemy_cooldowns[i] - Self::FIXED_DELTA).max(0.0);
+            if let (Some(enemy), Some(stats)) = (
+                self.ecs_manager.get_entity(enemy_id),
+                self.enemy_manager.get_enemy_stats(&self.enemy_types[i]),
+            ) {
+                let dx = (player_pos.0 - enemy.position_x) as f32;
+                let dy = (player_pos.1 - enemy.position_y) as f32;
+                if self.enemy_health[i] > 0 && self.enemy_cooldowns[i] <= 0.0 && (dx * dx + dy * dy).sqrt() <= stats.attack_range as f32 {
+                    animator.states.set_trigger("attack");
+                    self.enemy_cooldowns[i] = stats.attack_cooldown;
+                }
+            }
+            
+            animator.update(Self::FIXED_DELTA);
+            
+            if let Some(frame) = animator.animations.get_current_frame() {
+                if let Some(enemy) = self.ecs_manager.get_entity_mut(enemy_id) {
+                    enemy.set_sprite(frame.clone());
+                }
+                if let Some(instance) = renderer.get_sprite_instance_mut(enemy_id) {
+                    instance.sprite = frame.clone();
+                    instance.flip_horizontal = animator.animations.is_flipped();
+                }
+            }
+        }
+    }
+    
+    fn queue_debug_shapes(&self, debug: &mut DebugOverlay) {
+        if !debug.any_enabled() {
+            return;
+        }
+        
+        let cell_size = DebugOverlay::SPATIAL_CELL_SIZE;
+        let mut occupied_cells = HashSet::new();
+        
+        for entity in self.ecs_manager.iter_entities() {
+            let position = (entity.position_x, entity.position_y);
+            let mut label_y = entity.position_y;
+            
+            if let Some(bounds) = entity.get_collider_bounds() {
+                if entity.solid {
+                    debug.rect(DebugCategory::Colliders, bounds, 0xFF00FF00);
+                } else {
+                    debug.rect(DebugCategory::Sensors, bounds, 0xFFFFFF00);
+                }
+                
+                let (left, top, right, bottom) = bounds;
+                for cell_y in top.div_euclid(cell_size)..=(bottom - 1).div_euclid(cell_size) {
+                    for cell_x in left.div_euclid(cell_size)..=(right - 1).div_euclid(cell_size) {
+                        occupied_cells.insert((cell_x, cell_y));
+                    }
+                }
+                label_y = top - 8;
+            }
+            
+            if entity.velocity_x != 0.0 || entity.velocity_y != 0.0 {
+                let tip = (
+                    position.0 + (entity.velocity_x * 8.0) as i32,
+                    position.1 + (entity.velocity_y * 8.0) as i32,
+                );
+                debug.line(DebugCategory::Velocity, position, tip, 0xFF00C0FF);
+            }
+            
+            debug.text(DebugCategory::EntityIds, entity.position_x - 4, label_y, &format!("#{}", entity.id.0), 0xFFFFFFFF);
+        }
+        
+        if let Some(entity) = self.selected_entity.and_then(|id| self.ecs_manager.get_entity(id)) {
+            let bounds = entity.get_collider_bounds().unwrap_or((
+                entity.position_x - 4,
+                entity.position_y - 4,
+                entity.position_x + 4,
+                entity.position_y + 4,
+            ));
+            let (left, top, right, bottom) = bounds;
+            debug.rect(DebugCategory::EntityIds, (left - 2, top - 2, right + 2, bottom + 2), 0xFFFF00FF);
+        }
+        
+        for (cell_x, cell_y) in occupied_cells {
+            let left = cell_x * cell_size;
+            let top = cell_y * cell_size;
+            debug.rect(DebugCategory::SpatialHash, (left, top, left + cell_size, top + cell_size), 0xFF4060FF);
+        }
+        
+        // Line from each enemy to the player while the player is inside its aggro range
+        let player_pos = (self.player.entity.position_x, self.player.entity.position_y);
+        for (i, &enemy_id) in self.enemy_ids.iter().enumerate() {
+            let (enemy, stats) = match (
+                self.ecs_manager.get_entity(enemy_id),
+                self.enemy_manager.get_enemy_stats(&self.enemy_types[i]),
+            ) {
+                (Some(enemy), Some(stats)) => (enemy, stats),
+                _ => continue,
+            };
+            
+            let dx = (player_pos.0 - enemy.position_x) as f32;
+            let dy = (player_pos.1 - enemy.position_y) as f32;
+            if (dx * dx + dy * dy).sqrt() <= stats.aggro_range as f32 {
+                debug.line(DebugCategory::AiTargets, (enemy.position_x, enemy.position_y), player_pos, 0xFFFF4040);
+            }
+        }
+    }
+    
+    pub fn run(&mut self, renderer: &mut Renderer) {
+        let frame_duration = Duration::from_millis(1000 / Self::FPS);
+        
+        while renderer.is_open() {
+            let start = Instant::now();
+            
+            let delta = self.last_frame.elapsed().as_secs_f32();
+            self.last_frame = Instant::now();
+            
+            self.update(renderer, delta);
+            renderer.render_frame();
+            
+            let elapsed = start.elapsed();
+            if elapsed < frame_duration {
+                std::thread::sleep(frame_duration - elapsed);
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -1,7 +1,58 @@
-use std::fs;
-use serde_json::Value;
-
-pub fn load_json(path: &str) -> Value {
-    let content = fs::read_to_string(path).expect("Failed to read file");
-    serde_json::from_str(&content).expect("JSON was not well-formatted")
-}
+use std::fs;
+use serde_json::Value;
+
+pub fn load_json(path: &str) -> Value {
+    let content = fs::read_to_string(path).expect("Failed to read file");
+    serde_json::from_str(&content).expect("JSON was not well-formatted")
+}
+
+// Accepts "#RRGGBB" or "#AARRGGBB" and returns ARGB, opaque when no alpha is given
+pub fn parse_hex_color(text: &str) -> Option<u32> {
+    let hex = text.trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(0xFF000000 | value),
+        8 => Some(value),
+        _ => None,
+    }
+}
+
+pub fn lerp_color(from: u32, to: u32, t: f32) -> u32 {
+    let t = t.clamp(0.0, 1.0);
+    let mut result = 0;
+    for shift in [24, 16, 8, 0] {
+        let a = ((from >> shift) & 0xFF) as f32;
+        let b = ((to >> shift) & 0xFF) as f32;
+        result |= ((a + (b - a) * t).round() as u32) << shift;
+    }
+    result
+}
+
+// Small xorshift generator so effects stay reproducible from a seed
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        Self { state: if seed == 0 { 0x9E3779B9 } else { seed } }
+    }
+    
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+    
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+    
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// Polls modification times under a directory, no OS file notifications needed
+pub struct FileWatcher {
+    root: PathBuf,
+    poll_interval: Duration,
+    last_poll: Instant,
+    mtimes: HashMap<PathBuf, SystemTime>,
+}
+
+impl FileWatcher {
+    pub fn new(root: &str, poll_interval: Duration) -> Self {
+        let mut watcher = Self {
+            root: PathBuf::from(root),
+            poll_interval,
+            last_poll: Instant::now(),
+            mtimes: HashMap::new(),
+        };
+        watcher.mtimes = watcher.scan();
+        watcher
+    }
+    
+    // Files that appeared or were modified since the last poll, sorted by path.
+    // Returns nothing until poll_interval has passed so it is cheap to call every frame
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.poll_interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+        
+        let current = self.scan();
+        let mut changed: Vec<PathBuf> = current.iter()
+            .filter(|(path, mtime)| self.mtimes.get(*path) != Some(mtime))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+        
+        self.mtimes = current;
+        changed
+    }
+    
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut mtimes = HashMap::with_capacity(self.mtimes.len());
+        let mut pending = vec![self.root.clone()];
+        
+        while let Some(dir) = pending.pop() {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+                
+                if metadata.is_dir() {
+                    pending.push(path);
+                } else if let Ok(modified) = metadata.modified() {
+                    mtimes.insert(path, modified);
+                }
+            }
+        }
+        
+        mtimes
+    }
+}
+
+pub fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
\ No newline at end of file
@@ -0,0 +1,375 @@
+use minifb::{Key, MouseButton, Window};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+
+// Something that can hold an action down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    // Key names as minifb spells them ("W", "Up", "Space", "LeftShift", "Key1"),
+    // mouse buttons as "MouseLeft", "MouseMiddle" and "MouseRight". Case is ignored
+    pub fn from_name(name: &str) -> Option<Binding> {
+        match name.to_ascii_lowercase().as_str() {
+            "mouseleft" => return Some(Binding::Mouse(MouseButton::Left)),
+            "mousemiddle" => return Some(Binding::Mouse(MouseButton::Middle)),
+            "mouseright" => return Some(Binding::Mouse(MouseButton::Right)),
+            _ => {}
+        }
+        KEYS.iter()
+            .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+            .map(|&key| Binding::Key(key))
+    }
+    
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse{:?}", button),
+        }
+    }
+    
+    fn is_down(&self, window: &Window) -> bool {
+        match *self {
+            Binding::Key(key) => window.is_key_down(key),
+            Binding::Mouse(button) => window.get_mouse_down(button),
+        }
+    }
+}
+
+// Named actions like "move_up" or "attack" mapped to any number of keys and mouse buttons.
+// Gameplay asks about actions so layouts can change without touching it
+pub struct Input {
+    bindings: HashMap<String, Vec<Binding>>,
+    // Named sets of bindings that replace the listed actions, e.g. "azerty"
+    layouts: HashMap<String, HashMap<String, Vec<Binding>>>,
+    layout: Option<String>,
+    held: HashSet<String>,
+    previous: HashSet<String>,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+            layouts: HashMap::new(),
+            layout: None,
+            held: HashSet::new(),
+            previous: HashSet::new(),
+        }
+    }
+    
+    // Reads a bindings file as written by save_bindings:
+    //     {"layout": "azerty",
+    //      "bindings": {"move_up": ["W"], "attack": ["J", "MouseLeft"]},
+    //      "layouts": {"azerty": {"move_up": ["Z"], "move_left": ["Q"]}}}
+    // Replaces whatever was loaded before, so actions removed from the file go away on a hot reload
+    pub fn load_bindings(&mut self, path: &str) -> bool {
+        let settings: Value = match std::fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Failed to parse JSON {}: {}", path, e);
+                    return false;
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to read JSON file {}: {}", path, e);
+                return false;
+            }
+        };
+        
+        self.bindings.clear();
+        self.bindings.extend(parse_binding_map(&settings["bindings"], path));
+        self.layouts.clear();
+        if let Some(layouts) = settings["layouts"].as_object() {
+            for (name, layout) in layouts {
+                self.layouts.insert(name.clone(), parse_binding_map(layout, path));
+            }
+        }
+        if let Some(layout) = settings["layout"].as_str()
+            && !self.set_layout(layout) {
+            eprintln!("Unknown input layout {} in {}", layout, path);
+        }
+        true
+    }
+    
+    pub fn save_bindings(&self, path: &str) -> std::io::Result<()> {
+        let mut layouts = Map::new();
+        for (name, layout) in &self.layouts {
+            layouts.insert(name.clone(), binding_map_json(layout));
+        }
+        let settings = json!({
+            "layout": self.layout,
+            "bindings": binding_map_json(&self.bindings),
+            "layouts": layouts,
+        });
+        let text = serde_json::to_string_pretty(&settings).expect("bindings serialize");
+        std::fs::write(path, text)
+    }
+    
+    // Switches to one of the loaded layouts, replacing the bindings of the actions it lists
+    pub fn set_layout(&mut self, name: &str) -> bool {
+        let layout = match self.layouts.get(name) {
+            Some(layout) => layout,
+            None => return false,
+        };
+        for (action, bindings) in layout {
+            self.bindings.insert(action.clone(), bindings.clone());
+        }
+        self.layout = Some(name.to_string());
+        true
+    }
+    
+    pub fn layout(&self) -> Option<&str> {
+        self.layout.as_deref()
+    }
+    
+    // Layout names in alphabetical order
+    pub fn layout_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.layouts.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+    
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let mut bindings = self.bindings(action).to_vec();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self.set_bindings(action, bindings);
+    }
+    
+    // Also changes the active layout when it lists the action, or loading the saved file
+    // would put the layout's bindings back over the new ones
+    pub fn set_bindings(&mut self, action: &str, bindings: Vec<Binding>) {
+        if let Some(layout) = self.layout.as_ref().and_then(|name| self.layouts.get_mut(name))
+            && let Some(layout_bindings) = layout.get_mut(action) {
+            *layout_bindings = bindings.clone();
+        }
+        self.bindings.insert(action.to_string(), bindings);
+    }
+    
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.bindings.get(action).map(|b| b.as_slice()).unwrap_or(&[])
+    }
+    
+    // Call once per tick before gameplay asks about actions
+    pub fn update(&mut self, window: &Window) {
+        let held = self.bindings.iter()
+            .filter(|(_, bindings)| bindings.iter().any(|binding| binding.is_down(window)))
+            .map(|(action, _)| action.clone())
+            .collect();
+        self.set_held(held);
+    }
+    
+    // Stands in for update when the actions come from somewhere else, like a replay
+    pub fn set_held(&mut self, held: HashSet<String>) {
+        self.previous = std::mem::replace(&mut self.held, held);
+    }
+    
+    // Held actions in alphabetical order
+    pub fn held_actions(&self) -> Vec<String> {
+        let mut actions: Vec<String> = self.held.iter().cloned().collect();
+        actions.sort();
+        actions
+    }
+    
+    pub fn held(&self, action: &str) -> bool {
+        self.held.contains(action)
+    }
+    
+    // Went down since the last update
+    pub fn pressed(&self, action: &str) -> bool {
+        self.held.contains(action) && !self.previous.contains(action)
+    }
+    
+    // Came up since the last update
+    pub fn released(&self, action: &str) -> bool {
+        !self.held.contains(action) && self.previous.contains(action)
+    }
+    
+    // -1, 0 or 1 from a pair of opposing actions, 0 while both are held
+    pub fn axis(&self, negative: &str, positive: &str) -> f32 {
+        let mut value = 0.0;
+        if self.held(negative) { value -= 1.0; }
+        if self.held(positive) { value += 1.0; }
+        value
+    }
+}
+
+fn parse_binding_map(value: &Value, path: &str) -> HashMap<String, Vec<Binding>> {
+    let mut map = HashMap::new();
+    for (action, names) in value.as_object().into_iter().flatten() {
+        // A single name or a list of them
+        let names: Vec<&str> = match names {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(list) => list.iter().filter_map(|v| v.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        
+        let bindings = names.into_iter()
+            .filter_map(|name| {
+                let binding = Binding::from_name(name);
+                if binding.is_none() {
+                    eprintln!("Unknown key {} for action {} in {}", name, action, path);
+                }
+                binding
+            })
+            .collect();
+        map.insert(action.clone(), bindings);
+    }
+    map
+}
+
+fn binding_map_json(map: &HashMap<String, Vec<Binding>>) -> Value {
+    let mut actions = Map::new();
+    for (action, bindings) in map {
+        actions.insert(action.clone(), bindings.iter().map(|b| Value::String(b.name())).collect());
+    }
+    Value::Object(actions)
+}
+
+// Every key a binding can name, minifb has no way to list them
+const KEYS: [Key; 106] = [
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+    Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+    Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8,
+    Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15,
+    Key::Down, Key::Left, Key::Right, Key::Up,
+    Key::Apostrophe, Key::Backquote, Key::Backslash, Key::Comma, Key::Equal,
+    Key::LeftBracket, Key::Minus, Key::Period, Key::RightBracket, Key::Semicolon, Key::Slash,
+    Key::Backspace, Key::Delete, Key::End, Key::Enter, Key::Escape, Key::Home,
+    Key::Insert, Key::Menu, Key::PageDown, Key::PageUp, Key::Pause, Key::Space, Key::Tab,
+    Key::NumLock, Key::CapsLock, Key::ScrollLock,
+    Key::LeftShift, Key::RightShift, Key::LeftCtrl, Key::RightCtrl,
+    Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4,
+    Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9,
+    Key::NumPadDot, Key::NumPadSlash, Key::NumPadAsterisk, Key::NumPadMinus,
+    Key::NumPadPlus, Key::NumPadEnter,
+    Key::LeftAlt, Key::RightAlt, Key::LeftSuper, Key::RightSuper,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{Binding, Input};
+    use minifb::{Key, MouseButton};
+    use std::collections::HashSet;
+    
+    // Bindings file in the temp directory, unique per test so they can run in parallel
+    fn settings_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("input_test_{}_{}.json", std::process::id(), name));
+        std::fs::write(&path, content).expect("temp settings");
+        path.to_string_lossy().into_owned()
+    }
+    
+    fn hold(input: &mut Input, actions: &[&str]) {
+        input.set_held(actions.iter().map(|action| action.to_string()).collect::<HashSet<_>>());
+    }
+    
+    const SETTINGS: &str = r#"{
+        "layout": "qwerty",
+        "bindings": {"move_up": ["W"], "attack": ["J", "MouseLeft"], "map": "Tab"},
+        "layouts": {
+            "qwerty": {"move_up": ["W"]},
+            "azerty": {"move_up": ["Z"]}
+        }
+    }"#;
+    
+    #[test]
+    fn layouts_replace_the_actions_they_list() {
+        let path = settings_file("layouts", SETTINGS);
+        let mut input = Input::new();
+        assert!(input.load_bindings(&path));
+        assert_eq!(input.layout(), Some("qwerty"));
+        assert_eq!(input.layout_names(), vec!["azerty", "qwerty"]);
+        assert_eq!(input.bindings("map"), &[Binding::Key(Key::Tab)]);
+        
+        assert!(input.set_layout("azerty"));
+        assert_eq!(input.bindings("move_up"), &[Binding::Key(Key::Z)]);
+        assert_eq!(input.bindings("attack"), &[Binding::Key(Key::J), Binding::Mouse(MouseButton::Left)]);
+        assert!(!input.set_layout("dvorak"));
+        assert_eq!(input.layout(), Some("azerty"));
+        std::fs::remove_file(path).ok();
+    }
+    
+    #[test]
+    fn reloading_drops_removed_actions() {
+        let path = settings_file("reload", SETTINGS);
+        let mut input = Input::new();
+        input.load_bindings(&path);
+        
+        std::fs::write(&path, r#"{"bindings": {"move_up": ["Up"]}}"#).expect("rewrite settings");
+        assert!(input.load_bindings(&path));
+        assert_eq!(input.bindings("move_up"), &[Binding::Key(Key::Up)]);
+        assert!(input.bindings("attack").is_empty());
+        assert!(input.layout_names().is_empty());
+        std::fs::remove_file(path).ok();
+    }
+    
+    #[test]
+    fn rebinding_survives_a_save_and_load() {
+        let path = settings_file("save", SETTINGS);
+        let mut input = Input::new();
+        input.load_bindings(&path);
+        
+        input.set_bindings("move_up", vec![Binding::Key(Key::I)]);
+        input.bind("attack", Binding::Key(Key::K));
+        input.bind("attack", Binding::Key(Key::K));
+        input.save_bindings(&path).expect("save settings");
+        
+        let mut reloaded = Input::new();
+        assert!(reloaded.load_bindings(&path));
+        assert_eq!(reloaded.layout(), Some("qwerty"));
+        // The active layout was changed too, so it does not put W back
+        assert_eq!(reloaded.bindings("move_up"), &[Binding::Key(Key::I)]);
+        assert_eq!(
+            reloaded.bindings("attack"),
+            &[Binding::Key(Key::J), Binding::Mouse(MouseButton::Left), Binding::Key(Key::K)]
+        );
+        std::fs::remove_file(path).ok();
+    }
+    
+    #[test]
+    fn binding_names_round_trip() {
+        assert_eq!(Binding::from_name("leftshift"), Some(Binding::Key(Key::LeftShift)));
+        assert_eq!(Binding::from_name("MouseRight"), Some(Binding::Mouse(MouseButton::Right)));
+        assert_eq!(Binding::from_name("Hyper"), None);
+        for name in ["Key1", "Space", "NumPadEnter", "MouseMiddle"] {
+            assert_eq!(Binding::from_name(name).map(|binding| binding.name()).as_deref(), Some(name));
+        }
+    }
+    
+    #[test]
+    fn pressed_and_released_last_one_tick() {
+        let mut input = Input::new();
+        hold(&mut input, &["attack"]);
+        assert!(input.pressed("attack") && input.held("attack"));
+        
+        hold(&mut input, &["attack"]);
+        assert!(!input.pressed("attack") && input.held("attack"));
+        
+        hold(&mut input, &[]);
+        assert!(input.released("attack") && !input.held("attack"));
+        hold(&mut input, &[]);
+        assert!(!input.released("attack"));
+    }
+    
+    #[test]
+    fn axes_cancel_when_both_sides_are_held() {
+        let mut input = Input::new();
+        hold(&mut input, &["move_left"]);
+        assert_eq!(input.axis("move_left", "move_right"), -1.0);
+        hold(&mut input, &["move_right"]);
+        assert_eq!(input.axis("move_left", "move_right"), 1.0);
+        hold(&mut input, &["move_left", "move_right"]);
+        assert_eq!(input.axis("move_left", "move_right"), 0.0);
+        hold(&mut input, &[]);
+        assert_eq!(input.axis("move_left", "move_right"), 0.0);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::ecs::EntityID;
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LightID(pub usize);
+
+#[derive(Clone, Debug)]
+pub struct PointLight {
+    pub position_x: i32,
+    pub position_y: i32,
+    pub radius: f32,
+    pub color: u32,
+    pub intensity: f32,
+    // When attached the light follows the entity's sprite, offset by position_x/position_y
+    pub attached_to: Option<EntityID>,
+}
+
+impl PointLight {
+    pub fn new(radius: f32, color: u32) -> Self {
+        Self {
+            position_x: 0,
+            position_y: 0,
+            radius,
+            color,
+            intensity: 1.0,
+            attached_to: None,
+        }
+    }
+    
+    pub fn at(mut self, x: i32, y: i32) -> Self {
+        self.position_x = x;
+        self.position_y = y;
+        self
+    }
+    
+    pub fn attached(mut self, id: EntityID) -> Self {
+        self.attached_to = Some(id);
+        self
+    }
+    
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Occluder {
+    pub owner: Option<EntityID>,
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+pub struct Lighting {
+    pub enabled: bool,
+    // 0.0 is pitch black, 1.0 leaves the scene untouched
+    pub ambient: f32,
+    pub ambient_color: u32,
+    // Light map cell size in pixels, higher is faster but blockier
+    pub resolution: usize,
+    lights: BTreeMap<LightID, PointLight>,
+    occluders: Vec<Occluder>,
+    next_id: usize,
+    light_map: Vec<[f32; 3]>,
+    shadow_rays: Vec<f32>,
+}
+
+impl Lighting {
+    const SHADOW_RAYS: usize = 360;
+    
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            ambient: 1.0,
+            ambient_color: 0xFFFFFFFF,
+            resolution: 2,
+            lights: BTreeMap::new(),
+            occluders: Vec::new(),
+            next_id: 0,
+            light_map: Vec::new(),
+            shadow_rays: vec![0.0; Self::SHADOW_RAYS],
+        }
+    }
+    
+    pub fn add_light(&mut self, light: PointLight) -> LightID {
+        let id = LightID(self.next_id);
+        self.next_id += 1;
+        self.lights.insert(id, light);
+        id
+    }
+    
+    pub fn remove_lights_attached_to(&mut self, entity: EntityID) {
+        self.lights.retain(|_, light| light.attached_to != Some(entity));
+    }
+    
+    pub fn set_occluders(&mut self, occluders: impl IntoIterator<Item = Occluder>) {
+        self.occluders.clear();
+        self.occluders.extend(occluders);
+    }
+    
+    // Multiplies the frame by the accumulated light. `resolve` maps an entity to its world position
+    pub fn apply(
+        &mut self,
+        buffer: &mut [u32],
+        width: usize,
+        height: usize,
+        camera_x: i32,
+        camera_y: i32,
+        resolve: impl Fn(EntityID) -> Option<(i32, i32)>,
+    ) {
+        let res = self.resolution.max(1);
+        let map_w = width.div_ceil(res);
+        let map_h = height.div_ceil(res);
+        
+        let ambient = self.ambient.clamp(0.0, 1.0);
+        let base = [
+            ((self.ambient_color >> 16) & 0xFF) as f32 / 255.0 * ambient,
+            ((self.ambient_color >> 8) & 0xFF) as f32 / 255.0 * ambient,
+            (self.ambient_color & 0xFF) as f32 / 255.0 * ambient,
+        ];
+        self.light_map.clear();
+        self.light_map.resize(map_w * map_h, base);
+        
+        for light in self.lights.values() {
+            let (world_x, world_y) = match light.attached_to {
+                Some(id) => match resolve(id) {
+                    Some((x, y)) => (x + light.position_x, y + light.position_y),
+                    None => continue,
+                },
+                None => (light.position_x, light.position_y),
+            };
+            
+            let light_x = (world_x - camera_x) as f32;
+            let light_y = (world_y - camera_y) as f32;
+            let radius = light.radius.max(1.0);
+            
+            if light_x + radius < 0.0 || light_y + radius < 0.0
+                || light_x - radius > width as f32 || light_y - radius > height as f32 {
+                continue;
+            }
+            
+            Self::cast_shadows(
+                &mut self.shadow_rays,
+                &self.occluders,
+                light.attached_to,
+                world_x as f32,
+                world_y as f32,
+                radius,
+            );
+            
+            let color = [
+                ((light.color >> 16) & 0xFF) as f32 / 255.0 * light.intensity,
+                ((light.color >> 8) & 0xFF) as f32 / 255.0 * light.intensity,
+                (light.color & 0xFF) as f32 / 255.0 * light.intensity,
+            ];
+            
+            let min_cx = ((light_x - radius) / res as f32).floor().max(0.0) as usize;
+            let min_cy = ((light_y - radius) / res as f32).floor().max(0.0) as usize;
+            let max_cx = (((light_x + radius) / res as f32).ceil() as usize).min(map_w);
+            let max_cy = (((light_y + radius) / res as f32).ceil() as usize).min(map_h);
+            let ray_scale = Self::SHADOW_RAYS as f32 / (2.0 * PI);
+            
+            for cy in min_cy..max_cy {
+                let dy = (cy * res) as f32 + res as f32 * 0.5 - light_y;
+                let row = cy * map_w;
+                
+                for cx in min_cx..max_cx {
+                    let dx = (cx * res) as f32 + res as f32 * 0.5 - light_x;
+                    let dist_sq = dx * dx + dy * dy;
+                    if dist_sq >= radius * radius {
+                        continue;
+                    }
+                    
+                    let dist = dist_sq.sqrt();
+                    let ray = ((dy.atan2(dx) + PI) * ray_scale) as usize % Self::SHADOW_RAYS;
+                    if dist > self.shadow_rays[ray] {
+                        continue;
+                    }
+                    
+                    let falloff = 1.0 - dist / radius;
+                    let falloff = falloff * falloff;
+                    let cell = &mut self.light_map[row + cx];
+                    cell[0] += color[0] * falloff;
+                    cell[1] += color[1] * falloff;
+                    cell[2] += color[2] * falloff;
+                }
+            }
+        }
+        
+        for y in 0..height {
+            let map_row = (y / res) * map_w;
+            let row = y * width;
+            
+            for x in 0..width {
+                let cell = self.light_map[map_row + x / res];
+                let idx = row + x;
+                let pixel = buffer[idx];
+                
+                let r = (((pixel >> 16) & 0xFF) as f32 * cell[0].min(1.0)) as u32;
+                let g = (((pixel >> 8) & 0xFF) as f32 * cell[1].min(1.0)) as u32;
+                let b = ((pixel & 0xFF) as f32 * cell[2].min(1.0)) as u32;
+                buffer[idx] = (pixel & 0xFF000000) | (r << 16) | (g << 8) | b;
+            }
+        }
+    }
+    
+    // Fills `rays` with how far the light travels in each direction before an occluder blocks it
+    fn cast_shadows(
+        rays: &mut [f32],
+        occluders: &[Occluder],
+        owner: Option<EntityID>,
+        light_x: f32,
+        light_y: f32,
+        radius: f32,
+    ) {
+        rays.fill(radius);
+        
+        let nearby: Vec<&Occluder> = occluders.iter()
+            .filter(|o| owner.is_none() || o.owner != owner)
+            .filter(|o| {
+                let nearest_x = light_x.clamp(o.left as f32, o.right as f32);
+                let nearest_y = light_y.clamp(o.top as f32, o.bottom as f32);
+                let (dx, dy) = (nearest_x - light_x, nearest_y - light_y);
+                dx * dx + dy * dy < radius * radius
+            })
+            .collect();
+        
+        if nearby.is_empty() {
+            return;
+        }
+        
+        let count = rays.len();
+        for (i, ray) in rays.iter_mut().enumerate() {
+            // Same angle convention as the lookup in apply: bucket 0 starts at -PI
+            let angle = (i as f32 + 0.5) / count as f32 * 2.0 * PI - PI;
+            let (dir_y, dir_x) = angle.sin_cos();
+            
+            for o in &nearby {
+                if let Some(t) = Self::ray_box_distance(light_x, light_y, dir_x, dir_y, o) {
+                    *ray = ray.min(t);
+                }
+            }
+        }
+    }
+    
+    fn ray_box_distance(origin_x: f32, origin_y: f32, dir_x: f32, dir_y: f32, o: &Occluder) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::MAX;
+        
+        for (origin, dir, low, high) in [
+            (origin_x, dir_x, o.left as f32, o.right as f32),
+            (origin_y, dir_y, o.top as f32, o.bottom as f32),
+        ] {
+            if dir.abs() < 1e-6 {
+                if origin < low || origin > high {
+                    return None;
+                }
+            } else {
+                let t1 = (low - origin) / dir;
+                let t2 = (high - origin) / dir;
+                t_min = t_min.max(t1.min(t2));
+                t_max = t_max.min(t1.max(t2));
+                if t_min > t_max {
+                    return None;
+                }
+            }
+        }
+        
+        // Measure to the far side so the occluder itself stays lit and only what is behind it is shadowed
+        Some(t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lighting, Occluder, PointLight};
+    
+    fn lit_frame(lighting: &mut Lighting) -> Vec<u32> {
+        let mut buffer = vec![0xFFFFFFFF; 64 * 16];
+        lighting.apply(&mut buffer, 64, 16, 0, 0, |_| None);
+        buffer
+    }
+    
+    fn red(pixel: u32) -> u32 {
+        (pixel >> 16) & 0xFF
+    }
+    
+    #[test]
+    fn ambient_scales_every_channel_and_keeps_alpha() {
+        let mut lighting = Lighting::new();
+        lighting.ambient = 0.5;
+        lighting.resolution = 1;
+        
+        let buffer = lit_frame(&mut lighting);
+        assert!(buffer.iter().all(|&pixel| pixel == 0xFF7F7F7F));
+    }
+    
+    #[test]
+    fn light_falls_off_with_distance() {
+        let mut lighting = Lighting::new();
+        lighting.ambient = 0.0;
+        lighting.resolution = 1;
+        lighting.add_light(PointLight::new(20.0, 0xFFFFFFFF).at(8, 8));
+        
+        let buffer = lit_frame(&mut lighting);
+        let near = red(buffer[8 * 64 + 9]);
+        let far = red(buffer[8 * 64 + 20]);
+        assert!(near > far && far > 0);
+        assert_eq!(red(buffer[8 * 64 + 40]), 0);
+    }
+    
+    #[test]
+    fn occluders_shadow_what_is_behind_them() {
+        let mut lighting = Lighting::new();
+        lighting.ambient = 0.0;
+        lighting.resolution = 1;
+        lighting.add_light(PointLight::new(30.0, 0xFFFFFFFF).at(4, 8));
+        lighting.set_occluders([Occluder { owner: None, left: 10, top: 4, right: 14, bottom: 12 }]);
+        
+        let buffer = lit_frame(&mut lighting);
+        // In front of the wall and on its face is lit, behind it is dark
+        assert!(red(buffer[8 * 64 + 8]) > 0);
+        assert!(red(buffer[8 * 64 + 12]) > 0);
+        assert_eq!(red(buffer[8 * 64 + 20]), 0);
+    }
+    
+    #[test]
+    fn rays_stop_at_the_far_side_of_a_box() {
+        let wall = Occluder { owner: None, left: 10, top: -5, right: 20, bottom: 5 };
+        assert_eq!(Lighting::ray_box_distance(0.0, 0.0, 1.0, 0.0, &wall), Some(20.0));
+        assert_eq!(Lighting::ray_box_distance(0.0, 0.0, -1.0, 0.0, &wall), None);
+        assert_eq!(Lighting::ray_box_distance(0.0, 10.0, 1.0, 0.0, &wall), None);
+    }
+}
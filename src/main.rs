@@ -1,23 +1,122 @@
-mod sprite;
-mod renderer;
-mod ecs;
-mod camera;
-mod player;
-mod game;
-mod enemy_manager;
-mod animation_manager;
-mod helper_methods;
-mod scripting;
-
-use crate::game::Game;
-use crate::renderer::Renderer;
-
-fn main() {
-    let screen_width = 800;
-    let screen_height = 600;
-
-    let mut renderer = Renderer::new("Game Engine", screen_width as usize, screen_height as usize);    
-    let mut game = Game::new(&mut renderer, screen_width as i32, screen_height as i32);
-    
-    game.run(&mut renderer);
+mod sprite;
+mod assets;
+mod renderer;
+mod ecs;
+mod camera;
+mod player;
+mod game;
+mod enemy_manager;
+mod animation_manager;
+mod animation_state;
+mod aseprite;
+mod helper_methods;
+mod scripting;
+mod particles;
+mod lighting;
+mod render_layers;
+mod capture;
+mod debug_draw;
+mod hot_reload;
+mod input;
+mod replay;
+
+use crate::game::Game;
+use crate::renderer::Renderer;
+use crate::replay::Replay;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Per-channel difference still counted as the same pixel when checking a golden frame
+const GOLDEN_TOLERANCE: u8 = 2;
+
+//     cargo run                                   plays normally
+//     cargo run -- --record replay.json           also records every tick, saved when the window closes
+//     cargo run -- --replay replay.json [--golden frame.png]
+//                                                 plays a recording back without a window, exits with 1
+//                                                 when it ends somewhere else or the last frame differs
+fn main() {
+    let screen_width = 800;
+    let screen_height = 600;
+
+    let mut args = std::env::args().skip(1);
+    let mut record = None;
+    let mut replay = None;
+    let mut golden = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--record" => record = args.next(),
+            "--replay" => replay = args.next(),
+            "--golden" => golden = args.next(),
+            _ => eprintln!("Unknown argument {}", arg),
+        }
+    }
+
+    if let Some(path) = replay {
+        std::process::exit(run_replay(&path, golden.as_deref(), screen_width, screen_height));
+    }
+
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(1);
+    let mut renderer = Renderer::new("Game Engine", screen_width, screen_height);    
+    let mut game = Game::new(&mut renderer, screen_width as i32, screen_height as i32, seed);
+    if record.is_some() {
+        game.recording = Some(Replay::new(seed));
+    }
+    
+    game.run(&mut renderer);
+
+    if let (Some(path), Some(mut recording)) = (record, game.recording.take()) {
+        recording.checksum = Some(game.world_checksum());
+        match recording.save(&path) {
+            Ok(_) => println!("Saved {} ticks to {}", recording.tick_count(), path),
+            Err(e) => eprintln!("Failed to save replay {}: {}", path, e),
+        }
+    }
+}
+
+// Exit code for a headless replay, 0 when it ends on the recorded checksum and golden frame.
+// A golden image that does not exist yet is written from the last frame
+fn run_replay(path: &str, golden: Option<&str>, width: usize, height: usize) -> i32 {
+    let replay = match Replay::load(path) {
+        Some(replay) => replay,
+        None => return 1,
+    };
+
+    let mut renderer = Renderer::headless(width, height);
+    let mut game = Game::new(&mut renderer, width as i32, height as i32, replay.seed);
+    let checksum = game.play_replay(&mut renderer, &replay);
+    println!("Replayed {} ticks, world checksum {:016x}", replay.tick_count(), checksum);
+
+    let mut failed = false;
+    if let Some(expected) = replay.checksum
+        && expected != checksum {
+        eprintln!("Replay diverged, it was recorded ending on {:016x}", expected);
+        failed = true;
+    }
+
+    if let Some(golden) = golden {
+        renderer.render_frame();
+        if !Path::new(golden).exists() {
+            match renderer.save_screenshot(golden) {
+                Ok(_) => println!("Saved new golden frame {}", golden),
+                Err(e) => {
+                    eprintln!("Failed to save golden frame {}: {}", golden, e);
+                    failed = true;
+                }
+            }
+        } else {
+            match renderer.diff_against_png(golden, GOLDEN_TOLERANCE) {
+                Some(0) => println!("Last frame matches {}", golden),
+                Some(mismatched) => {
+                    eprintln!("{} pixels differ from {}", mismatched, golden);
+                    failed = true;
+                }
+                None => {
+                    eprintln!("Golden frame {} has a different size", golden);
+                    failed = true;
+                }
+            }
+        }
+    }
+
+    if failed { 1 } else { 0 }
 }
\ No newline at end of file
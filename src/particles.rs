@@ -0,0 +1,271 @@
+use crate::assets::{Assets, Handle, SpriteKey};
+use crate::ecs::{ECSManager, EntityID};
+use crate::helper_methods::{lerp_color, parse_hex_color, Rng};
+use crate::renderer::{ParticleInstance, Renderer};
+use crate::render_layers::Layer;
+use crate::sprite::Sprite;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EmitterID(pub usize);
+
+#[derive(Clone, Copy, Debug)]
+pub enum EmitterAnchor {
+    Entity { id: EntityID, offset_x: f32, offset_y: f32 },
+    Point { x: f32, y: f32 },
+}
+
+#[derive(Clone)]
+pub struct EmitterConfig {
+    pub spawn_rate: f32,
+    pub burst: u32,
+    pub duration: Option<f32>,
+    pub max_particles: usize,
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    pub direction: f32,
+    pub spread: f32,
+    pub gravity: f32,
+    pub color_start: u32,
+    pub color_end: u32,
+    pub size: u32,
+    pub sprite: Option<Handle<Sprite>>,
+    pub z_order: i32,
+    pub layer: Layer,
+}
+
+impl EmitterConfig {
+    pub fn from_json(info: &Value, assets: &mut Assets) -> Self {
+        let range = |key: &str, default: f32| -> (f32, f32) {
+            match &info[key] {
+                Value::Array(values) if values.len() == 2 => (
+                    values[0].as_f64().unwrap_or(default as f64) as f32,
+                    values[1].as_f64().unwrap_or(default as f64) as f32,
+                ),
+                value => {
+                    let v = value.as_f64().unwrap_or(default as f64) as f32;
+                    (v, v)
+                }
+            }
+        };
+        
+        let with_alpha = |color: u32, alpha: f64| -> u32 {
+            ((alpha.clamp(0.0, 1.0) * 255.0).round() as u32) << 24 | (color & 0x00FFFFFF)
+        };
+        
+        let color_start = info["color_start"].as_str().and_then(parse_hex_color).unwrap_or(0xFFFFFFFF);
+        let color_end = info["color_end"].as_str().and_then(parse_hex_color).unwrap_or(color_start);
+        let alpha_start = info["alpha_start"].as_f64().unwrap_or(1.0);
+        let alpha_end = info["alpha_end"].as_f64().unwrap_or(alpha_start);
+        
+        let sprite = info["sprite"].as_str().map(|path| {
+            let scale = info["scale"].as_u64().unwrap_or(1) as u32;
+            assets.sprite(SpriteKey::new(path).scale(scale))
+        });
+        
+        EmitterConfig {
+            spawn_rate: info["spawn_rate"].as_f64().unwrap_or(0.0) as f32,
+            burst: info["burst"].as_u64().unwrap_or(0) as u32,
+            duration: info["duration"].as_f64().map(|d| d as f32),
+            max_particles: info["max_particles"].as_u64().unwrap_or(256) as usize,
+            lifetime: range("lifetime", 1.0),
+            speed: range("speed", 0.0),
+            direction: info["direction"].as_f64().unwrap_or(-90.0) as f32,
+            spread: info["spread"].as_f64().unwrap_or(360.0) as f32,
+            gravity: info["gravity"].as_f64().unwrap_or(0.0) as f32,
+            color_start: with_alpha(color_start, alpha_start),
+            color_end: with_alpha(color_end, alpha_end),
+            size: info["size"].as_u64().unwrap_or(1) as u32,
+            sprite,
+            z_order: info["z_order"].as_i64().unwrap_or(0) as i32,
+            layer: info["layer"].as_str().and_then(Layer::from_name).unwrap_or(Layer::Entities),
+        }
+    }
+}
+
+struct Particle {
+    x: f32,
+    y: f32,
+    velocity_x: f32,
+    velocity_y: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+pub struct ParticleEmitter {
+    pub config: EmitterConfig,
+    pub anchor: EmitterAnchor,
+    pub active: bool,
+    elapsed: f32,
+    spawn_accumulator: f32,
+    pending_burst: u32,
+    orphaned: bool,
+    particles: Vec<Particle>,
+}
+
+impl ParticleEmitter {
+    pub fn new(config: EmitterConfig, anchor: EmitterAnchor) -> Self {
+        let pending_burst = config.burst;
+        Self {
+            config,
+            anchor,
+            active: true,
+            elapsed: 0.0,
+            spawn_accumulator: 0.0,
+            pending_burst,
+            orphaned: false,
+            particles: Vec::new(),
+        }
+    }
+    
+    fn spawn_particle(&mut self, origin_x: f32, origin_y: f32, rng: &mut Rng) {
+        if self.particles.len() >= self.config.max_particles {
+            return;
+        }
+        
+        let half_spread = self.config.spread / 2.0;
+        let angle = (self.config.direction + rng.range(-half_spread, half_spread)).to_radians();
+        let speed = rng.range(self.config.speed.0, self.config.speed.1);
+        
+        self.particles.push(Particle {
+            x: origin_x,
+            y: origin_y,
+            velocity_x: angle.cos() * speed,
+            velocity_y: angle.sin() * speed,
+            age: 0.0,
+            lifetime: rng.range(self.config.lifetime.0, self.config.lifetime.1).max(0.01),
+        });
+    }
+    
+    fn update(&mut self, origin: Option<(f32, f32)>, delta: f32, rng: &mut Rng) {
+        self.elapsed += delta;
+        
+        match origin {
+            Some((x, y)) => {
+                for _ in 0..self.pending_burst {
+                    self.spawn_particle(x, y, rng);
+                }
+                self.pending_burst = 0;
+                
+                let emitting = self.active && self.config.duration.is_none_or(|d| self.elapsed < d);
+                if emitting && self.config.spawn_rate > 0.0 {
+                    self.spawn_accumulator += self.config.spawn_rate * delta;
+                    while self.spawn_accumulator >= 1.0 {
+                        self.spawn_accumulator -= 1.0;
+                        self.spawn_particle(x, y, rng);
+                    }
+                }
+            }
+            // The entity we were attached to is gone, let the remaining particles fade out
+            None => self.orphaned = true,
+        }
+        
+        let gravity = self.config.gravity;
+        for particle in &mut self.particles {
+            particle.age += delta;
+            particle.velocity_y += gravity * delta;
+            particle.x += particle.velocity_x * delta;
+            particle.y += particle.velocity_y * delta;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+    }
+    
+    fn is_finished(&self) -> bool {
+        let expired = self.orphaned || self.config.duration.is_some_and(|d| self.elapsed >= d);
+        expired && self.pending_burst == 0 && self.particles.is_empty()
+    }
+}
+
+pub struct ParticleSystem {
+    definitions: HashMap<String, EmitterConfig>,
+    emitters: BTreeMap<EmitterID, ParticleEmitter>,
+    next_id: usize,
+    rng: Rng,
+}
+
+impl ParticleSystem {
+    pub fn new(json_path: &str, seed: u32, assets: &mut Assets) -> Self {
+        let mut definitions = HashMap::new();
+        
+        match std::fs::read_to_string(json_path) {
+            Ok(content) => match serde_json::from_str::<Value>(&content) {
+                Ok(Value::Object(entries)) => {
+                    for (name, info) in entries {
+                        if name != "comments" {
+                            definitions.insert(name, EmitterConfig::from_json(&info, assets));
+                        }
+                    }
+                }
+                Ok(_) => eprintln!("Particle settings {} must be a JSON object", json_path),
+                Err(e) => eprintln!("Failed to parse JSON {}: {}", json_path, e),
+            },
+            Err(e) => eprintln!("Failed to read JSON file {}: {}", json_path, e),
+        }
+        
+        Self {
+            definitions,
+            emitters: BTreeMap::new(),
+            next_id: 0,
+            rng: Rng::new(seed),
+        }
+    }
+    
+    pub fn spawn_emitter(&mut self, name: &str, anchor: EmitterAnchor) -> Option<EmitterID> {
+        let config = match self.definitions.get(name) {
+            Some(config) => config.clone(),
+            None => {
+                eprintln!("Unknown particle emitter: {}", name);
+                return None;
+            }
+        };
+        
+        let id = EmitterID(self.next_id);
+        self.next_id += 1;
+        self.emitters.insert(id, ParticleEmitter::new(config, anchor));
+        Some(id)
+    }
+    
+    pub fn burst_at(&mut self, name: &str, x: i32, y: i32) -> Option<EmitterID> {
+        self.spawn_emitter(name, EmitterAnchor::Point { x: x as f32, y: y as f32 })
+    }
+    
+    pub fn set_active(&mut self, id: EmitterID, active: bool) {
+        if let Some(emitter) = self.emitters.get_mut(&id) {
+            emitter.active = active;
+        }
+    }
+    
+    pub fn update(&mut self, ecs_manager: &ECSManager, delta: f32) {
+        let rng = &mut self.rng;
+        
+        self.emitters.retain(|_, emitter| {
+            let origin = match emitter.anchor {
+                EmitterAnchor::Entity { id, offset_x, offset_y } => ecs_manager.get_entity(id)
+                    .map(|e| (e.position_x as f32 + offset_x, e.position_y as f32 + offset_y)),
+                EmitterAnchor::Point { x, y } => Some((x, y)),
+            };
+            
+            emitter.update(origin, delta, rng);
+            !emitter.is_finished()
+        });
+    }
+    
+    pub fn submit(&self, renderer: &mut Renderer) {
+        for emitter in self.emitters.values() {
+            let config = &emitter.config;
+            for particle in &emitter.particles {
+                let t = particle.age / particle.lifetime;
+                renderer.queue_particle(ParticleInstance {
+                    position_x: particle.x.round() as i32,
+                    position_y: particle.y.round() as i32,
+                    color: lerp_color(config.color_start, config.color_end, t),
+                    size: config.size,
+                    sprite: config.sprite.clone(),
+                    z_order: config.z_order,
+                    layer: config.layer,
+                });
+            }
+        }
+    }
+}
\ No newline at end of file
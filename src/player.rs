@@ -1,138 +1,247 @@
-use crate::ecs::{ECSManager, Entity, EntityID};
-use crate::renderer::Renderer;
-use crate::animation_manager::AnimationHandler;
-use minifb::Key;
-
-pub struct Player {
-    pub entity_id: EntityID,
-    pub entity: Entity,
-    pub movement_speed: f32,
-    pub input_dx: f32,
-    pub input_dy: f32,
-    pub animations: AnimationHandler,
-    pub facing: String,
-}
-
-impl Player {
-    pub fn new(renderer: &mut Renderer) -> Self {
-        let entity_id = EntityID(0);
-        let mut entity = Entity::new(entity_id, "player", 100, 100, 2)
-            .with_mass(2.0);
-        
-        let mut animations = AnimationHandler::new();
-        let states = [
-            ("idle", 6, 0.15),
-            ("walking", 8, 0.2),
-            ("jump", 3, 0.15),
-            ("hurt", 4, 0.1),
-            ("death", 4, 0.15),
-        ];
-        
-        for &(state, frames, speed) in &states {
-            let frame_sprites = AnimationHandler::load_sprite_sheet(
-                &format!("assets/sprites/player/{}_animation.png", state),
-                100, 100, frames, 2,
-            );
-            animations.add_animation(state, frame_sprites, speed, true);
-        }
-        
-        animations.set_state("idle");
-        
-        let sprite = animations.get_current_frame().unwrap();
-        let collider_width = sprite.width() / 3;
-        let collider_height = sprite.height() / 3;
-        entity.set_collider_centered(collider_width, collider_height);
-        
-        renderer.add_sprite_instance(entity_id, crate::renderer::SpriteInstance {
-            sprite: sprite.clone(),
-            position_x: entity.position_x,
-            position_y: entity.position_y,
-            z_order: entity.z_order,
-            flip_horizontal: false,
-        });
-        
-        Player {
-            entity_id,
-            entity,
-            movement_speed: 5.0,
-            input_dx: 0.0,
-            input_dy: 0.0,
-            animations,
-            facing: "right".to_string(),
-        }
-    }
-    
-    #[inline]
-    fn process_input(&mut self, renderer: &Renderer) {
-        self.input_dx = 0.0;
-        self.input_dy = 0.0;
-        
-        if renderer.window.is_key_down(Key::W) { self.input_dy -= 1.0; }
-        if renderer.window.is_key_down(Key::S) { self.input_dy += 1.0; }
-        if renderer.window.is_key_down(Key::A) { 
-            self.input_dx -= 1.0; 
-            self.facing = "left".to_string();
-        }
-        if renderer.window.is_key_down(Key::D) { 
-            self.input_dx += 1.0; 
-            self.facing = "right".to_string();
-        }
-        
-        if self.input_dx != 0.0 || self.input_dy != 0.0 {
-            let length = (self.input_dx * self.input_dx + self.input_dy * self.input_dy).sqrt();
-            self.input_dx = self.input_dx / length * self.movement_speed;
-            self.input_dy = self.input_dy / length * self.movement_speed;
-        }
-    }
-    
-    fn process_movement(&mut self, ecs_manager: &mut ECSManager) {
-        if ecs_manager.move_entity(self.entity_id, self.input_dx, self.input_dy) {
-            // Update local entity copy from ECS
-            if let Some(updated) = ecs_manager.get_entity(self.entity_id) {
-                self.entity.position_x = updated.position_x;
-                self.entity.position_y = updated.position_y;
-            }
-        }
-    }
-    
-    fn draw_hitbox_outline(&self, renderer: &mut Renderer) {
-        if let Some((left, top, right, bottom)) = self.entity.get_collider_bounds() {
-            let width = (right - left) as u32;
-            let height = (bottom - top) as u32;
-            let screen_left = left - renderer.camera_x;
-            let screen_top = top - renderer.camera_y;
-            renderer.draw_rectangle(screen_left, screen_top, width, height, 0xFF00FF00, 3);
-            renderer.draw_filled_rectangle(screen_left, screen_top, width, height, 0x2200FF00);
-        }
-    }
-    
-    fn update_renderer(&mut self, renderer: &mut Renderer) {
-        self.animations.update();
-        
-        if let Some(frame) = self.animations.get_current_frame() {
-            self.entity.set_sprite(frame.clone());
-            
-            renderer.move_sprite(self.entity_id, self.entity.position_x, self.entity.position_y);
-            
-            if let Some(instance) = renderer.get_sprite_instance_mut(self.entity_id) {
-                instance.sprite = frame.clone();
-                instance.flip_horizontal = self.facing == "left";
-            }
-        }
-        
-        self.draw_hitbox_outline(renderer);
-    }
-    
-    pub fn update(&mut self, renderer: &mut Renderer, ecs_manager: &mut ECSManager) {
-        self.process_input(renderer);
-        self.process_movement(ecs_manager);
-        
-        if self.input_dx.abs() > 0.0 || self.input_dy.abs() > 0.0 {
-            self.animations.set_state("walking");
-        } else {
-            self.animations.set_state("idle");
-        }
-        
-        self.update_renderer(renderer);
-    }
+use crate::assets::{Assets, Pivot, SpriteKey};
+use crate::aseprite::AsepriteSheet;
+use crate::ecs::{ECSManager, Entity, EntityID};
+use crate::renderer::Renderer;
+use crate::animation_manager::{AnimationHandler, Direction, GridLayout, LoopDirection};
+use crate::animation_state::AnimationStateMachine;
+use crate::helper_methods::load_json;
+use crate::render_layers::Layer;
+use crate::input::Input;
+
+// Input actions driving one player, so two players can share one keyboard
+#[derive(Clone, Copy, Debug)]
+pub struct Controls {
+    pub up: &'static str,
+    pub down: &'static str,
+    pub left: &'static str,
+    pub right: &'static str,
+    pub attack: &'static str,
+}
+
+impl Controls {
+    pub const PLAYER_ONE: Controls = Controls {
+        up: "move_up",
+        down: "move_down",
+        left: "move_left",
+        right: "move_right",
+        attack: "attack",
+    };
+    pub const PLAYER_TWO: Controls = Controls {
+        up: "p2_move_up",
+        down: "p2_move_down",
+        left: "p2_move_left",
+        right: "p2_move_right",
+        attack: "p2_attack",
+    };
+    
+    // In the order rebinding asks for them
+    pub fn actions(&self) -> [&'static str; 5] {
+        [self.up, self.down, self.left, self.right, self.attack]
+    }
+}
+
+pub struct Player {
+    pub entity_id: EntityID,
+    pub entity: Entity,
+    pub movement_speed: f32,
+    pub input_dx: f32,
+    pub input_dy: f32,
+    pub animations: AnimationHandler,
+    // Set "speed", "is_hurt" and "is_dead" on this instead of picking animations directly
+    pub states: AnimationStateMachine,
+    // Last direction moved in, picks the directional clip
+    pub facing: Direction,
+    pub controls: Controls,
+    pub health: i32,
+}
+
+impl Player {
+    // Aseprite JSON export, one tag per animation with footsteps in the tag user data
+    const ASEPRITE_SHEET: &str = "assets/sprites/player/player.json";
+    // Weapon swings packed one per row in 100x100 cells with a border and gap around each:
+    // sword, second sword, bow
+    const ATTACK_SHEET: &str = "assets/sprites/player/weapons/attacking_basic_sheet.png";
+    const ATTACK_CLIPS: [(&str, usize); 3] = [("attack", 6), ("attack_sword2", 6), ("attack_bow", 9)];
+    const ATTACK_FRAME_SIZE: u32 = 100;
+    const ATTACK_FRAME_DURATION: f32 = 0.07;
+    // Swings land on this frame, see Game for who gets hit
+    const ATTACK_HIT_FRAME: usize = 3;
+    const MAX_HEALTH: i32 = 100;
+    const SPRITE_SCALE: u32 = 2;
+    const STATES: &str = "assets/settings/player_states.json";
+    // Top-down movement picks from 8 directions, 4-way sets fall back to the nearest clip
+    const EIGHT_WAY: bool = true;
+    
+    pub fn new(renderer: &mut Renderer, assets: &mut Assets, entity_id: EntityID, x: i32, y: i32, controls: Controls) -> Self {
+        let mut entity = Entity::new(entity_id, "player", x, y, 2)
+            .with_mass(2.0);
+        
+        let mut animations = AnimationHandler::new();
+        let sheet = AsepriteSheet::load(Self::ASEPRITE_SHEET).expect("player sprite sheet");
+        let (pivot_x, pivot_y, body_width, body_height) = Self::load_aseprite(assets, &sheet, &mut animations);
+        Self::load_attacks(assets, &mut animations, pivot_x, pivot_y);
+        
+        let mut states = AnimationStateMachine::from_json(&load_json(Self::STATES)["states"], "idle")
+            .expect("player animation states");
+        states.start(&mut animations);
+        
+        entity.set_collider(body_width, body_height, -(body_width as i32) / 2, -(body_height as i32));
+        let sort_offset_y = entity.collider.as_ref()
+            .map(|c| c.offset_y + c.height as i32)
+            .unwrap_or(0);
+        
+        let sprite = animations.get_current_frame().unwrap();
+        renderer.add_sprite_instance(entity_id, crate::renderer::SpriteInstance {
+            sprite: sprite.clone(),
+            position_x: entity.position_x,
+            position_y: entity.position_y,
+            z_order: entity.z_order,
+            flip_horizontal: false,
+            layer: Layer::Entities,
+            sort_offset_y,
+            viewport: None,
+        });
+        
+        Player {
+            entity_id,
+            entity,
+            movement_speed: 5.0,
+            input_dx: 0.0,
+            input_dy: 0.0,
+            animations,
+            states,
+            facing: Direction::Right,
+            controls,
+            health: Self::MAX_HEALTH,
+        }
+    }
+    
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+    
+    // Returns true when this hit is the one that kills
+    pub fn take_damage(&mut self, amount: i32) -> bool {
+        if self.is_dead() {
+            return false;
+        }
+        self.health -= amount;
+        if self.is_dead() {
+            self.states.set_bool("is_dead", true);
+            true
+        } else {
+            self.states.set_trigger("is_hurt");
+            false
+        }
+    }
+    
+    // Anchor every frame at the character's feet and size the collider to the body, both measured
+    // from the opaque pixels of the idle pose. Returns the pivot and the body size
+    fn measure_body(assets: &mut Assets, idle: SpriteKey, offset_x: u32, offset_y: u32) -> (i32, i32, u32, u32) {
+        let idle = assets.sprite(idle);
+        let idle = idle.get();
+        let (body_x, body_y, body_width, body_height) = idle.opaque_bounds()
+            .unwrap_or((0, 0, idle.width(), idle.height()));
+        let pivot_x = (offset_x + body_x + body_width / 2) as i32;
+        let pivot_y = (offset_y + body_y + body_height) as i32;
+        (pivot_x, pivot_y, body_width, body_height)
+    }
+    
+    fn load_aseprite(assets: &mut Assets, sheet: &AsepriteSheet, animations: &mut AnimationHandler) -> (i32, i32, u32, u32) {
+        let scale = Self::SPRITE_SCALE;
+        // Trimmed frames sit at an offset inside the untrimmed cell
+        let idle_index = sheet.tag("idle").map(|tag| tag.from).unwrap_or(0);
+        let idle_frame = &sheet.frames[idle_index];
+        let body = Self::measure_body(
+            assets,
+            sheet.frame_key(idle_index, scale, None),
+            idle_frame.offset_x * scale,
+            idle_frame.offset_y * scale,
+        );
+        sheet.populate(assets, animations, scale, Some(&Pivot::At(body.0, body.1)), true);
+        body
+    }
+    
+    // The swings share the body's pivot so the character does not jump when one starts
+    fn load_attacks(assets: &mut Assets, animations: &mut AnimationHandler, pivot_x: i32, pivot_y: i32) {
+        let layout = GridLayout::new(Self::ATTACK_FRAME_SIZE, Self::ATTACK_FRAME_SIZE).with_margin(2).with_spacing(2);
+        let sheet = SpriteKey::new(Self::ATTACK_SHEET).scale(Self::SPRITE_SCALE).pivot_at(pivot_x, pivot_y);
+        let cells = AnimationHandler::load_sprite_grid(assets, &sheet, &layout, None);
+        let columns = Self::ATTACK_CLIPS.iter().map(|&(_, count)| count).max().unwrap_or(0);
+        for (row, &(name, count)) in Self::ATTACK_CLIPS.iter().enumerate() {
+            let frames: Vec<_> = cells.iter().skip(row * columns).take(count).cloned().collect();
+            animations.add_animation(name, frames, vec![Self::ATTACK_FRAME_DURATION; count], LoopDirection::Forward, None);
+            animations.add_event(name, Self::ATTACK_HIT_FRAME, "hit");
+        }
+    }
+    
+    #[inline]
+    fn process_input(&mut self, input: &Input) {
+        let controls = self.controls;
+        self.input_dx = input.axis(controls.left, controls.right);
+        self.input_dy = input.axis(controls.up, controls.down);
+        if input.pressed(controls.attack) {
+            self.states.set_trigger("attack");
+        }
+        
+        // Standing still keeps facing the last way moved
+        if let Some(direction) = Direction::from_vector(self.input_dx, self.input_dy, Self::EIGHT_WAY) {
+            self.facing = direction;
+        }
+        
+        if self.input_dx != 0.0 || self.input_dy != 0.0 {
+            let length = (self.input_dx * self.input_dx + self.input_dy * self.input_dy).sqrt();
+            self.input_dx = self.input_dx / length * self.movement_speed;
+            self.input_dy = self.input_dy / length * self.movement_speed;
+        }
+    }
+    
+    fn process_movement(&mut self, ecs_manager: &mut ECSManager) {
+        // Velocity is in pixels per tick, the same units as the input
+        self.entity.velocity_x = self.input_dx;
+        self.entity.velocity_y = self.input_dy;
+        if let Some(entity) = ecs_manager.get_entity_mut(self.entity_id) {
+            entity.velocity_x = self.input_dx;
+            entity.velocity_y = self.input_dy;
+        }
+        
+        if ecs_manager.move_entity(self.entity_id, self.input_dx, self.input_dy) {
+            // Update local entity copy from ECS
+            if let Some(updated) = ecs_manager.get_entity(self.entity_id) {
+                self.entity.position_x = updated.position_x;
+                self.entity.position_y = updated.position_y;
+            }
+        }
+    }
+    
+    fn update_renderer(&mut self, renderer: &mut Renderer, delta: f32) {
+        self.animations.set_facing(self.facing);
+        self.states.update(&mut self.animations, delta);
+        
+        if let Some(frame) = self.animations.get_current_frame() {
+            self.entity.set_sprite(frame.clone());
+            
+            renderer.move_sprite(self.entity_id, self.entity.position_x, self.entity.position_y);
+            
+            if let Some(instance) = renderer.get_sprite_instance_mut(self.entity_id) {
+                instance.sprite = frame.clone();
+                instance.flip_horizontal = self.animations.is_flipped();
+            }
+        }
+    }
+    
+    pub fn update(&mut self, input: &Input, renderer: &mut Renderer, ecs_manager: &mut ECSManager, delta: f32) {
+        if self.is_dead() {
+            self.input_dx = 0.0;
+            self.input_dy = 0.0;
+        } else {
+            self.process_input(input);
+        }
+        self.process_movement(ecs_manager);
+        
+        let speed = (self.input_dx * self.input_dx + self.input_dy * self.input_dy).sqrt();
+        self.states.set_float("speed", speed);
+        
+        self.update_renderer(renderer, delta);
+    }
 }
\ No newline at end of file
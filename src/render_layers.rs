@@ -0,0 +1,186 @@
+use crate::assets::{Assets, Handle, SpriteKey};
+use crate::sprite::Sprite;
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Layer {
+    Background,
+    World,
+    Entities,
+    Foreground,
+    Ui,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 5] = [Layer::Background, Layer::World, Layer::Entities, Layer::Foreground, Layer::Ui];
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Background => "background",
+            Layer::World => "world",
+            Layer::Entities => "entities",
+            Layer::Foreground => "foreground",
+            Layer::Ui => "ui",
+        }
+    }
+    
+    pub fn from_name(name: &str) -> Option<Layer> {
+        Layer::ALL.iter().copied().find(|layer| layer.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortMode {
+    ZOrder,
+    // Top-down depth: sprites whose feet are lower on screen are drawn in front
+    FootY,
+}
+
+impl SortMode {
+    pub fn from_name(name: &str) -> Option<SortMode> {
+        match name {
+            "z_order" => Some(SortMode::ZOrder),
+            "y" | "foot_y" => Some(SortMode::FootY),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LayerSettings {
+    pub parallax_x: f32,
+    pub parallax_y: f32,
+    // Screen-space layers ignore the camera entirely, used for UI
+    pub screen_space: bool,
+    pub visible: bool,
+    pub sort_mode: SortMode,
+}
+
+impl LayerSettings {
+    pub fn new(layer: Layer) -> Self {
+        Self {
+            parallax_x: 1.0,
+            parallax_y: 1.0,
+            screen_space: layer == Layer::Ui,
+            visible: true,
+            sort_mode: SortMode::ZOrder,
+        }
+    }
+    
+    pub fn camera_offset(&self, camera_x: i32, camera_y: i32) -> (i32, i32) {
+        if self.screen_space {
+            (0, 0)
+        } else {
+            (
+                (camera_x as f32 * self.parallax_x).round() as i32,
+                (camera_y as f32 * self.parallax_y).round() as i32,
+            )
+        }
+    }
+}
+
+pub struct TiledBackground {
+    pub sprite: Handle<Sprite>,
+    pub layer: Layer,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+}
+
+impl TiledBackground {
+    pub fn draw(&self, buffer: &mut [u32], buf_w: usize, buf_h: usize, camera_x: i32, camera_y: i32) {
+        let sprite = self.sprite.get();
+        let tile_w = sprite.width() as i32;
+        let tile_h = sprite.height() as i32;
+        if tile_w == 0 || tile_h == 0 {
+            return;
+        }
+        
+        let origin_x = self.offset_x - camera_x;
+        let origin_y = self.offset_y - camera_y;
+        
+        // Snap the first tile to just left/above the view so the pattern scrolls seamlessly
+        let (start_x, end_x) = if self.repeat_x {
+            (origin_x.rem_euclid(tile_w) - tile_w, buf_w as i32)
+        } else {
+            (origin_x, origin_x + 1)
+        };
+        let (start_y, end_y) = if self.repeat_y {
+            (origin_y.rem_euclid(tile_h) - tile_h, buf_h as i32)
+        } else {
+            (origin_y, origin_y + 1)
+        };
+        
+        let mut y = start_y;
+        while y < end_y {
+            let mut x = start_x;
+            while x < end_x {
+                sprite.draw_at(buffer, buf_w, buf_h, x, y, false);
+                x += tile_w;
+            }
+            y += tile_h;
+        }
+    }
+}
+
+pub fn load_layer_config(path: &str, assets: &mut Assets) -> (Vec<(Layer, LayerSettings)>, Vec<TiledBackground>) {
+    let config: Value = match std::fs::read_to_string(path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Failed to parse JSON {}: {}", path, e);
+                return (Vec::new(), Vec::new());
+            }
+        },
+        Err(e) => {
+            eprintln!("Failed to read JSON file {}: {}", path, e);
+            return (Vec::new(), Vec::new());
+        }
+    };
+    
+    let mut layers = Vec::new();
+    if let Some(entries) = config["layers"].as_object() {
+        for (name, info) in entries {
+            let layer = match Layer::from_name(name) {
+                Some(layer) => layer,
+                None => {
+                    eprintln!("Unknown render layer in {}: {}", path, name);
+                    continue;
+                }
+            };
+            
+            let defaults = LayerSettings::new(layer);
+            let parallax = &info["parallax"];
+            layers.push((layer, LayerSettings {
+                parallax_x: parallax[0].as_f64().map(|p| p as f32).unwrap_or(defaults.parallax_x),
+                parallax_y: parallax[1].as_f64().map(|p| p as f32).unwrap_or(defaults.parallax_y),
+                screen_space: info["screen_space"].as_bool().unwrap_or(defaults.screen_space),
+                visible: info["visible"].as_bool().unwrap_or(defaults.visible),
+                sort_mode: info["sort"].as_str().and_then(SortMode::from_name).unwrap_or(defaults.sort_mode),
+            }));
+        }
+    }
+    
+    let mut backgrounds = Vec::new();
+    if let Some(entries) = config["backgrounds"].as_array() {
+        for info in entries {
+            let sprite_path = match info["sprite"].as_str() {
+                Some(path) => path,
+                None => continue,
+            };
+            let scale = info["scale"].as_u64().unwrap_or(1) as u32;
+            
+            backgrounds.push(TiledBackground {
+                sprite: assets.sprite(SpriteKey::new(sprite_path).scale(scale)),
+                layer: info["layer"].as_str().and_then(Layer::from_name).unwrap_or(Layer::Background),
+                offset_x: info["offset_x"].as_i64().unwrap_or(0) as i32,
+                offset_y: info["offset_y"].as_i64().unwrap_or(0) as i32,
+                repeat_x: info["repeat_x"].as_bool().unwrap_or(true),
+                repeat_y: info["repeat_y"].as_bool().unwrap_or(true),
+            });
+        }
+    }
+    
+    (layers, backgrounds)
+}
\ No newline at end of file
@@ -1,149 +1,661 @@
-use crate::sprite::Sprite;
-use minifb::{Window, WindowOptions, Scale};
-use std::collections::HashMap;
-use crate::ecs::EntityID;
-
-pub struct SpriteInstance {
-    pub sprite: Sprite,
-    pub position_x: i32,
-    pub position_y: i32,
-    pub z_order: i32,
-    pub flip_horizontal: bool,
-}
-
-pub struct Renderer {
-    pub window: Window,
-    buffer: Vec<u32>,
-    pub width: usize,
-    pub height: usize,
-    sprites: HashMap<EntityID, SpriteInstance>,
-    pub camera_x: i32,
-    pub camera_y: i32,
-}
-
-impl Renderer {
-    pub fn new(title: &str, width: usize, height: usize) -> Self {
-        let window = Window::new(
-            title,
-            width,
-            height,
-            WindowOptions {
-                resize: true,
-                scale: Scale::X1,
-                ..WindowOptions::default()
-            },
-        ).unwrap();
-
-        Self {
-            window,
-            buffer: vec![0; width * height],
-            width,
-            height,
-            sprites: HashMap::with_capacity(64),
-            camera_x: 0,
-            camera_y: 0,
-        }
-    }
-
-    pub fn set_camera(&mut self, x: i32, y: i32) {
-        self.camera_x = x;
-        self.camera_y = y;
-    }
-
-    pub fn add_sprite_instance(&mut self, id: EntityID, sprite_instance: SpriteInstance) {
-        self.sprites.insert(id, sprite_instance);
-    }
-
-    pub fn move_sprite(&mut self, id: EntityID, x: i32, y: i32) {
-        if let Some(s) = self.sprites.get_mut(&id) {
-            s.position_x = x;
-            s.position_y = y;
-        }
-    }
-
-    pub fn get_sprite_instance_mut(&mut self, id: EntityID) -> Option<&mut SpriteInstance> {
-        self.sprites.get_mut(&id)
-    }
-
-    pub fn draw_rectangle(&mut self, left: i32, top: i32, width: u32, height: u32, color: u32, thickness: i32) {
-        let right = left + width as i32;
-        let bottom = top + height as i32;
-
-        for i in 0..thickness {
-            self.draw_line(left, top + i, right, top + i, color);
-            self.draw_line(left, bottom - i - 1, right, bottom - i - 1, color);
-            self.draw_line(left + i, top, left + i, bottom, color);
-            self.draw_line(right - i - 1, top, right - i - 1, bottom, color);
-        }
-    }
-
-    pub fn draw_filled_rectangle(&mut self, left: i32, top: i32, width: u32, height: u32, color: u32) {
-        let right = (left + width as i32).min(self.width as i32);
-        let bottom = (top + height as i32).min(self.height as i32);
-
-        for y in top.max(0)..bottom {
-            let row = y as usize * self.width;
-            for x in left.max(0)..right {
-                let idx = row + x as usize;
-                if idx < self.buffer.len() {
-                    self.buffer[idx] = color;
-                }
-            }
-        }
-    }
-
-    pub fn draw_line(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, color: u32) {
-        let dx = (x2 - x1).abs();
-        let dy = (y2 - y1).abs();
-        let sx = if x1 < x2 { 1 } else { -1 };
-        let sy = if y1 < y2 { 1 } else { -1 };
-        let mut err = dx - dy;
-
-        let mut x = x1;
-        let mut y = y1;
-
-        loop {
-            if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
-                self.buffer[y as usize * self.width + x as usize] = color;
-            }
-
-            if x == x2 && y == y2 {
-                break;
-            }
-
-            let e2 = err * 2;
-            if e2 > -dy {
-                err -= dy;
-                x += sx;
-            }
-            if e2 < dx {
-                err += dx;
-                y += sy;
-            }
-        }
-    }
-
-    pub fn render_frame(&mut self) {
-        self.buffer.fill(0x00000000);
-
-        let mut ordered: Vec<&SpriteInstance> = self.sprites.values().collect();
-        ordered.sort_by_key(|s| s.z_order);
-
-        for s in ordered {
-            let x = s.position_x - self.camera_x;
-            let y = s.position_y - self.camera_y;
-
-            if s.flip_horizontal {
-                s.sprite.draw_flipped(&mut self.buffer, self.width, self.height, x, y);
-            } else {
-                s.sprite.draw(&mut self.buffer, self.width, self.height, x, y);
-            }
-        }
-
-        let _ = self.window.update_with_buffer(&self.buffer, self.width, self.height);
-    }
-
-    pub fn is_open(&self) -> bool {
-        self.window.is_open()
-    }
+use crate::assets::{Assets, Handle};
+use crate::sprite::{Sprite, blend_pixel};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions, Scale, ScaleMode};
+use std::collections::HashMap;
+use crate::ecs::EntityID;
+use crate::camera::Camera;
+use crate::lighting::Lighting;
+use crate::capture::{self, FrameCapture};
+use crate::debug_draw::DebugOverlay;
+use crate::render_layers::{Layer, LayerSettings, SortMode, TiledBackground, load_layer_config};
+
+pub struct SpriteInstance {
+    pub sprite: Handle<Sprite>,
+    pub position_x: i32,
+    pub position_y: i32,
+    pub z_order: i32,
+    pub flip_horizontal: bool,
+    pub layer: Layer,
+    // Distance from position_y down to the sprite's feet, used by y-sorted layers
+    pub sort_offset_y: i32,
+    // Index into Renderer::viewports to only draw in that view, e.g. one player's HUD.
+    // None draws in every view
+    pub viewport: Option<usize>,
+}
+
+pub struct ParticleInstance {
+    pub position_x: i32,
+    pub position_y: i32,
+    pub color: u32,
+    pub size: u32,
+    pub sprite: Option<Handle<Sprite>>,
+    pub z_order: i32,
+    pub layer: Layer,
+}
+
+enum DrawItem<'a> {
+    Sprite(EntityID, &'a SpriteInstance),
+    Particle(&'a ParticleInstance),
+}
+
+impl DrawItem<'_> {
+    fn layer(&self) -> Layer {
+        match self {
+            DrawItem::Sprite(_, s) => s.layer,
+            DrawItem::Particle(p) => p.layer,
+        }
+    }
+
+    fn z_order(&self) -> i32 {
+        match self {
+            DrawItem::Sprite(_, s) => s.z_order,
+            DrawItem::Particle(p) => p.z_order,
+        }
+    }
+
+    fn foot_y(&self) -> i32 {
+        match self {
+            DrawItem::Sprite(_, s) => s.position_y + s.sort_offset_y,
+            DrawItem::Particle(p) => p.position_y,
+        }
+    }
+
+    // Keeps equal keys in a stable order between frames, particles go after sprites
+    fn tiebreak(&self) -> usize {
+        match self {
+            DrawItem::Sprite(id, _) => id.0,
+            DrawItem::Particle(_) => usize::MAX,
+        }
+    }
+}
+
+// Sort key of an item, later keys are drawn on top
+fn draw_key(layers: &[LayerSettings], item: &DrawItem) -> (Layer, i32, usize) {
+    let layer = item.layer();
+    let depth = match layers[layer as usize].sort_mode {
+        SortMode::ZOrder => item.z_order(),
+        SortMode::FootY => item.foot_y(),
+    };
+    (layer, depth, item.tiebreak())
+}
+
+// One camera's view drawn into a rectangle of the logical screen
+#[derive(Clone, Debug)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    // Copy of the camera seen through, placed at the view's corner. Its zoom is screen pixels
+    // per world pixel: everything below the UI is drawn into a buffer at 1/zoom of the
+    // view's size and stretched over it before the UI goes on top
+    pub camera: Camera,
+    // Small views like a minimap leave out the UI layer and debug shapes
+    pub show_ui: bool,
+    pub lighting: bool,
+    // Outline drawn on the view's edge
+    pub border: Option<u32>,
+}
+
+impl Viewport {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        let mut camera = Camera::new(width as i32, height as i32);
+        camera.viewport_x = x as i32;
+        camera.viewport_y = y as i32;
+        Self {
+            x,
+            y,
+            width,
+            height,
+            camera,
+            show_ui: true,
+            lighting: true,
+            border: None,
+        }
+    }
+
+    pub fn with_camera(mut self, camera: &Camera) -> Self {
+        self.camera = camera.clone();
+        self.camera.viewport_x = self.x as i32;
+        self.camera.viewport_y = self.y as i32;
+        self
+    }
+
+    pub fn with_border(mut self, color: u32) -> Self {
+        self.border = Some(color);
+        self
+    }
+
+    pub fn contains(&self, screen_x: f32, screen_y: f32) -> bool {
+        screen_x >= self.x as f32 && screen_y >= self.y as f32
+            && screen_x < (self.x + self.width) as f32 && screen_y < (self.y + self.height) as f32
+    }
+
+    fn zoom(&self) -> f32 {
+        if self.camera.zoom > 0.0 { self.camera.zoom } else { 1.0 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickMode {
+    // Anywhere inside the entity's collider
+    Collider,
+    // Only on visible pixels of its sprite
+    SpriteAlpha,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizeMode {
+    // Keep the internal resolution and present it with whole-number scaling and black bars
+    IntegerScaled,
+    // Grow the logical viewport to match the window
+    Expand,
+}
+
+pub struct Renderer {
+    // None when running headless, e.g. for replays. Frames are still drawn, just not shown
+    pub window: Option<Window>,
+    pub resize_mode: ResizeMode,
+    base_width: usize,
+    base_height: usize,
+    present_buffer: Vec<u32>,
+    present_scale: usize,
+    present_offset: (usize, usize),
+    buffer: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+    sprites: HashMap<EntityID, SpriteInstance>,
+    particles: Vec<ParticleInstance>,
+    // Drawn in order, later views on top. Starts as one view covering the whole screen
+    pub viewports: Vec<Viewport>,
+    // Scratch buffers each view is drawn through before it is copied into its rectangle
+    view_buffer: Vec<u32>,
+    world_buffer: Vec<u32>,
+    pub lighting: Lighting,
+    layers: Vec<LayerSettings>,
+    backgrounds: Vec<TiledBackground>,
+    pub capture: FrameCapture,
+    pub debug: DebugOverlay,
+}
+
+impl Renderer {
+    pub fn new(title: &str, width: usize, height: usize) -> Self {
+        let window = Window::new(
+            title,
+            width,
+            height,
+            WindowOptions {
+                resize: true,
+                scale: Scale::X1,
+                scale_mode: ScaleMode::UpperLeft,
+                ..WindowOptions::default()
+            },
+        ).unwrap();
+
+        let mut renderer = Self::headless(width, height);
+        renderer.window = Some(window);
+        renderer
+    }
+
+    pub fn headless(width: usize, height: usize) -> Self {
+        Self {
+            window: None,
+            resize_mode: ResizeMode::IntegerScaled,
+            base_width: width,
+            base_height: height,
+            present_buffer: Vec::new(),
+            present_scale: 1,
+            present_offset: (0, 0),
+            buffer: vec![0; width * height],
+            width,
+            height,
+            sprites: HashMap::with_capacity(64),
+            particles: Vec::with_capacity(512),
+            viewports: vec![Viewport::new(0, 0, width, height)],
+            view_buffer: Vec::new(),
+            world_buffer: Vec::new(),
+            lighting: Lighting::new(),
+            layers: Layer::ALL.iter().map(|&layer| LayerSettings::new(layer)).collect(),
+            backgrounds: Vec::new(),
+            capture: FrameCapture::new("captures"),
+            debug: DebugOverlay::new(),
+        }
+    }
+
+    // Call once per frame before drawing. Returns the new logical size when it changed
+    pub fn handle_resize(&mut self) -> Option<(usize, usize)> {
+        let (window_width, window_height) = self.window.as_ref()?.get_size();
+        if window_width == 0 || window_height == 0 {
+            return None;
+        }
+
+        let (width, height) = match self.resize_mode {
+            ResizeMode::IntegerScaled => (self.base_width, self.base_height),
+            ResizeMode::Expand => (window_width, window_height),
+        };
+
+        if width == self.width && height == self.height {
+            return None;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.buffer = vec![0; width * height];
+        Some((width, height))
+    }
+
+    pub fn load_layers(&mut self, path: &str, assets: &mut Assets) {
+        let (layers, backgrounds) = load_layer_config(path, assets);
+        for (layer, settings) in layers {
+            self.layers[layer as usize] = settings;
+        }
+        self.backgrounds.extend(backgrounds);
+    }
+
+    pub fn layer_settings_mut(&mut self, layer: Layer) -> &mut LayerSettings {
+        &mut self.layers[layer as usize]
+    }
+
+    pub fn set_layer_visible(&mut self, layer: Layer, visible: bool) {
+        self.layers[layer as usize].visible = visible;
+    }
+
+    pub fn add_sprite_instance(&mut self, id: EntityID, sprite_instance: SpriteInstance) {
+        self.sprites.insert(id, sprite_instance);
+    }
+
+    pub fn move_sprite(&mut self, id: EntityID, x: i32, y: i32) {
+        if let Some(s) = self.sprites.get_mut(&id) {
+            s.position_x = x;
+            s.position_y = y;
+        }
+    }
+
+    pub fn get_sprite_instance_mut(&mut self, id: EntityID) -> Option<&mut SpriteInstance> {
+        self.sprites.get_mut(&id)
+    }
+
+    pub fn remove_sprite_instance(&mut self, id: EntityID) {
+        self.sprites.remove(&id);
+    }
+
+    pub fn queue_particle(&mut self, particle: ParticleInstance) {
+        self.particles.push(particle);
+    }
+
+    pub fn draw_rectangle(&mut self, left: i32, top: i32, width: u32, height: u32, color: u32, thickness: i32) {
+        draw_rectangle_into(&mut self.buffer, self.width, self.height, left, top, width, height, color, thickness);
+    }
+
+    pub fn draw_filled_rectangle(&mut self, left: i32, top: i32, width: u32, height: u32, color: u32) {
+        draw_filled_rectangle_into(&mut self.buffer, self.width, self.height, left, top, width, height, color);
+    }
+
+    pub fn draw_line(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, color: u32) {
+        draw_line_into(&mut self.buffer, self.width, self.height, x1, y1, x2, y2, color);
+    }
+
+    pub fn render_frame(&mut self) {
+        self.buffer.fill(0x00000000);
+
+        let mut ordered: Vec<DrawItem> = self.sprites.iter().map(|(&id, s)| DrawItem::Sprite(id, s))
+            .chain(self.particles.iter().map(DrawItem::Particle))
+            .collect();
+        let layers = &self.layers;
+        ordered.sort_by_key(|item| draw_key(layers, item));
+
+        for (index, viewport) in self.viewports.iter().enumerate() {
+            // Clip the view to the screen, it may still be laid out for the old size after a resize
+            let width = viewport.width.min(self.width.saturating_sub(viewport.x));
+            let height = viewport.height.min(self.height.saturating_sub(viewport.y));
+            if width == 0 || height == 0 {
+                continue;
+            }
+
+            self.view_buffer.clear();
+            self.view_buffer.resize(width * height, 0);
+
+            let zoom = viewport.zoom();
+            let (view_x, view_y) = viewport.camera.render_offset();
+            let zoomed = zoom != 1.0;
+            let world_width = ((width as f32 / zoom).ceil() as usize).max(1);
+            let world_height = ((height as f32 / zoom).ceil() as usize).max(1);
+            if zoomed {
+                self.world_buffer.clear();
+                self.world_buffer.resize(world_width * world_height, 0);
+            }
+
+            let mut items = ordered.iter().peekable();
+
+            for layer in Layer::ALL {
+                if layer == Layer::Ui {
+                    let (world, world_width, world_height) = if zoomed {
+                        (&mut self.world_buffer, world_width, world_height)
+                    } else {
+                        (&mut self.view_buffer, width, height)
+                    };
+
+                    // Light the world before the UI goes on top so HUD elements stay readable
+                    if self.lighting.enabled && viewport.lighting {
+                        let sprites = &self.sprites;
+                        self.lighting.apply(
+                            world,
+                            world_width,
+                            world_height,
+                            view_x,
+                            view_y,
+                            |id| sprites.get(&id).map(|s| (s.position_x, s.position_y)),
+                        );
+                    }
+
+                    // Debug shapes live in world space, so they share the entity layer's camera
+                    if self.debug.any_enabled() && viewport.show_ui {
+                        let (camera_x, camera_y) = self.layers[Layer::Entities as usize].camera_offset(view_x, view_y);
+                        self.debug.draw(world, world_width, world_height, camera_x, camera_y);
+                    }
+
+                    if zoomed {
+                        stretch_into(&self.world_buffer, world_width, world_height, &mut self.view_buffer, width, height, zoom);
+                    }
+                }
+
+                let (target, target_width, target_height) = if zoomed && layer != Layer::Ui {
+                    (&mut self.world_buffer, world_width, world_height)
+                } else {
+                    (&mut self.view_buffer, width, height)
+                };
+
+                let settings = &self.layers[layer as usize];
+                let (camera_x, camera_y) = settings.camera_offset(view_x, view_y);
+                let visible = settings.visible && (layer != Layer::Ui || viewport.show_ui);
+
+                if visible {
+                    for background in self.backgrounds.iter().filter(|b| b.layer == layer) {
+                        background.draw(target, target_width, target_height, camera_x, camera_y);
+                    }
+                }
+
+                while let Some(item) = items.next_if(|item| item.layer() == layer) {
+                    if !visible {
+                        continue;
+                    }
+
+                    match item {
+                        DrawItem::Sprite(_, s) => {
+                            if s.viewport.is_some_and(|only| only != index) {
+                                continue;
+                            }
+                            let x = s.position_x - camera_x;
+                            let y = s.position_y - camera_y;
+
+                            if s.flip_horizontal {
+                                s.sprite.get().draw_flipped(target, target_width, target_height, x, y);
+                            } else {
+                                s.sprite.get().draw(target, target_width, target_height, x, y);
+                            }
+                        }
+                        DrawItem::Particle(p) => {
+                            let x = p.position_x - camera_x;
+                            let y = p.position_y - camera_y;
+
+                            match &p.sprite {
+                                Some(sprite) => sprite.get().draw_with_alpha(target, target_width, target_height, x, y, p.color >> 24),
+                                None => Self::blend_square(target, target_width, target_height, x, y, p.size, p.color),
+                            }
+                        }
+                    }
+                }
+            }
+
+            if let Some(color) = viewport.border {
+                draw_rectangle_into(&mut self.view_buffer, width, height, 0, 0, width as u32, height as u32, color, 1);
+            }
+
+            for row in 0..height {
+                let src = &self.view_buffer[row * width..(row + 1) * width];
+                let dst_start = (viewport.y + row) * self.width + viewport.x;
+                self.buffer[dst_start..dst_start + width].copy_from_slice(src);
+            }
+        }
+
+        self.particles.clear();
+
+        self.capture.record(&self.buffer, self.width, self.height);
+        self.present();
+    }
+
+    // Window pixel to a point of the logical screen, undoing the integer scale and letterbox bars.
+    // None over the bars
+    pub fn window_to_screen(&self, window_x: f32, window_y: f32) -> Option<(f32, f32)> {
+        let scale = self.present_scale.max(1) as f32;
+        let screen_x = (window_x - self.present_offset.0 as f32) / scale;
+        let screen_y = (window_y - self.present_offset.1 as f32) / scale;
+        let inside = screen_x >= 0.0 && screen_y >= 0.0 && screen_x < self.width as f32 && screen_y < self.height as f32;
+        inside.then_some((screen_x, screen_y))
+    }
+
+    pub fn mouse_screen_position(&self) -> Option<(f32, f32)> {
+        let (window_x, window_y) = self.window.as_ref()?.get_mouse_pos(MouseMode::Pass)?;
+        self.window_to_screen(window_x, window_y)
+    }
+
+    // The topmost view under a point of the logical screen and the point relative to its corner
+    pub fn viewport_at(&self, screen_x: f32, screen_y: f32) -> Option<(usize, f32, f32)> {
+        self.viewports.iter().enumerate().rev()
+            .find(|(_, viewport)| viewport.contains(screen_x, screen_y))
+            .map(|(index, viewport)| (index, screen_x - viewport.x as f32, screen_y - viewport.y as f32))
+    }
+
+    // World position under a point of the logical screen, seen through the view it falls in
+    pub fn screen_to_world(&self, screen_x: f32, screen_y: f32) -> Option<(f32, f32)> {
+        let (index, _, _) = self.viewport_at(screen_x, screen_y)?;
+        Some(self.viewports[index].camera.screen_to_world(screen_x, screen_y))
+    }
+
+    // Topmost sprite under a point of the logical screen, seen through the view it falls in
+    // and honouring each layer's parallax and the zoom. `collider` gives an entity's collider
+    // bounds for PickMode::Collider
+    pub fn pick(
+        &self,
+        screen_x: f32,
+        screen_y: f32,
+        mode: PickMode,
+        collider: impl Fn(EntityID) -> Option<(i32, i32, i32, i32)>,
+    ) -> Option<EntityID> {
+        let (index, local_x, local_y) = self.viewport_at(screen_x, screen_y)?;
+        let viewport = &self.viewports[index];
+        let (view_x, view_y) = viewport.camera.render_offset();
+
+        let mut ordered: Vec<DrawItem> = self.sprites.iter().map(|(&id, s)| DrawItem::Sprite(id, s)).collect();
+        ordered.sort_by_key(|item| draw_key(&self.layers, item));
+
+        ordered.into_iter().rev().find_map(|item| {
+            let (id, s) = match item {
+                DrawItem::Sprite(id, s) => (id, s),
+                DrawItem::Particle(_) => return None,
+            };
+            let settings = &self.layers[s.layer as usize];
+            if !settings.visible || s.viewport.is_some_and(|only| only != index) {
+                return None;
+            }
+            if s.layer == Layer::Ui && !viewport.show_ui {
+                return None;
+            }
+
+            // The UI is drawn at full resolution, everything else through the zoom
+            let zoom = if s.layer == Layer::Ui { 1.0 } else { viewport.zoom() };
+            let (camera_x, camera_y) = settings.camera_offset(view_x, view_y);
+            let x = (camera_x as f32 + local_x / zoom).floor() as i32;
+            let y = (camera_y as f32 + local_y / zoom).floor() as i32;
+
+            let hit = match mode {
+                PickMode::Collider => collider(id)
+                    .is_some_and(|(left, top, right, bottom)| x >= left && x < right && y >= top && y < bottom),
+                PickMode::SpriteAlpha => s.sprite.get().covers(s.position_x, s.position_y, x, y, s.flip_horizontal),
+            };
+            hit.then_some(id)
+        })
+    }
+
+    pub fn save_screenshot(&self, path: &str) -> Result<(), image::ImageError> {
+        capture::save_png(&self.buffer, self.width, self.height, path)
+    }
+
+    // Pixels of the last frame that differ from a golden PNG, see capture::diff_against_png
+    pub fn diff_against_png(&self, path: &str, tolerance: u8) -> Option<usize> {
+        capture::diff_against_png(&self.buffer, self.width, self.height, path, tolerance)
+    }
+
+    // False without a window
+    pub fn is_key_pressed(&self, key: Key) -> bool {
+        self.window.as_ref().is_some_and(|window| window.is_key_pressed(key, KeyRepeat::No))
+    }
+
+    // Keys that went down since the last window update
+    pub fn keys_pressed(&self) -> Vec<Key> {
+        self.window.as_ref().map(|window| window.get_keys_pressed(KeyRepeat::No)).unwrap_or_default()
+    }
+
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.window.as_ref().is_some_and(|window| window.get_mouse_down(button))
+    }
+
+    fn present(&mut self) {
+        let window = match &mut self.window {
+            Some(window) => window,
+            None => return,
+        };
+        let (window_width, window_height) = window.get_size();
+        let scale = (window_width / self.width).min(window_height / self.height);
+
+        // Nothing to scale, or the window is smaller than the internal resolution
+        if scale == 0 || (window_width == self.width && window_height == self.height) {
+            self.present_scale = 1;
+            self.present_offset = (0, 0);
+            let _ = window.update_with_buffer(&self.buffer, self.width, self.height);
+            return;
+        }
+
+        let scaled_width = self.width * scale;
+        let scaled_height = self.height * scale;
+        let offset_x = (window_width - scaled_width) / 2;
+        let offset_y = (window_height - scaled_height) / 2;
+        self.present_scale = scale;
+        self.present_offset = (offset_x, offset_y);
+
+        self.present_buffer.clear();
+        self.present_buffer.resize(window_width * window_height, 0);
+
+        for y in 0..self.height {
+            let src_row = &self.buffer[y * self.width..(y + 1) * self.width];
+            let dst_start = (offset_y + y * scale) * window_width + offset_x;
+
+            let dst_row = &mut self.present_buffer[dst_start..dst_start + scaled_width];
+            for (x, &color) in src_row.iter().enumerate() {
+                dst_row[x * scale..(x + 1) * scale].fill(color);
+            }
+
+            // Repeat the finished row for the rest of the scale factor
+            for repeat in 1..scale {
+                let target = dst_start + repeat * window_width;
+                self.present_buffer.copy_within(dst_start..dst_start + scaled_width, target);
+            }
+        }
+
+        let _ = window.update_with_buffer(&self.present_buffer, window_width, window_height);
+    }
+
+    fn blend_square(buffer: &mut [u32], buf_w: usize, buf_h: usize, cx: i32, cy: i32, size: u32, color: u32) {
+        let left = cx - size as i32 / 2;
+        let top = cy - size as i32 / 2;
+
+        for y in top.max(0)..(top + size as i32).min(buf_h as i32) {
+            let row = y as usize * buf_w;
+            for x in left.max(0)..(left + size as i32).min(buf_w as i32) {
+                let idx = row + x as usize;
+                buffer[idx] = blend_pixel(buffer[idx], color);
+            }
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.as_ref().is_some_and(|window| window.is_open())
+    }
+}
+
+// Nearest-neighbour stretch of a view drawn at 1/zoom resolution over the whole destination
+#[allow(clippy::too_many_arguments)]
+fn stretch_into(source: &[u32], source_width: usize, source_height: usize, buffer: &mut [u32], buf_w: usize, buf_h: usize, zoom: f32) {
+    let columns: Vec<usize> = (0..buf_w)
+        .map(|x| ((x as f32 / zoom) as usize).min(source_width - 1))
+        .collect();
+
+    for y in 0..buf_h {
+        let source_y = ((y as f32 / zoom) as usize).min(source_height - 1);
+        let source_row = &source[source_y * source_width..(source_y + 1) * source_width];
+        let row = &mut buffer[y * buf_w..(y + 1) * buf_w];
+        for (pixel, &source_x) in row.iter_mut().zip(&columns) {
+            *pixel = source_row[source_x];
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_rectangle_into(buffer: &mut [u32], buf_w: usize, buf_h: usize, left: i32, top: i32, width: u32, height: u32, color: u32, thickness: i32) {
+    let right = left + width as i32;
+    let bottom = top + height as i32;
+
+    for i in 0..thickness {
+        draw_line_into(buffer, buf_w, buf_h, left, top + i, right, top + i, color);
+        draw_line_into(buffer, buf_w, buf_h, left, bottom - i - 1, right, bottom - i - 1, color);
+        draw_line_into(buffer, buf_w, buf_h, left + i, top, left + i, bottom, color);
+        draw_line_into(buffer, buf_w, buf_h, right - i - 1, top, right - i - 1, bottom, color);
+    }
+}
+
+// Translucent colors are blended over what is already in the buffer
+#[allow(clippy::too_many_arguments)]
+pub fn draw_filled_rectangle_into(buffer: &mut [u32], buf_w: usize, buf_h: usize, left: i32, top: i32, width: u32, height: u32, color: u32) {
+    let right = (left + width as i32).min(buf_w as i32);
+    let bottom = (top + height as i32).min(buf_h as i32);
+
+    for y in top.max(0)..bottom {
+        let row = y as usize * buf_w;
+        for x in left.max(0)..right {
+            let idx = row + x as usize;
+            buffer[idx] = blend_pixel(buffer[idx], color);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_line_into(buffer: &mut [u32], buf_w: usize, buf_h: usize, x1: i32, y1: i32, x2: i32, y2: i32, color: u32) {
+    let dx = (x2 - x1).abs();
+    let dy = (y2 - y1).abs();
+    let sx = if x1 < x2 { 1 } else { -1 };
+    let sy = if y1 < y2 { 1 } else { -1 };
+    let mut err = dx - dy;
+
+    let mut x = x1;
+    let mut y = y1;
+
+    loop {
+        if x >= 0 && y >= 0 && x < buf_w as i32 && y < buf_h as i32 {
+            buffer[y as usize * buf_w + x as usize] = color;
+        }
+
+        if x == x2 && y == y2 {
+            break;
+        }
+
+        let e2 = err * 2;
+        if e2 > -dy {
+            err -= dy;
+            x += sx;
+        }
+        if e2 < dx {
+            err += dx;
+            y += sy;
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,107 @@
+use crate::input::Input;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+// The RNG seed and the actions held on every tick, enough to play a session back
+// through Game::fixed_update and end up in the same world state
+pub struct Replay {
+    pub seed: u32,
+    ticks: Vec<Vec<String>>,
+    // Game::world_checksum after the last tick, compared against on playback
+    pub checksum: Option<u64>,
+}
+
+impl Replay {
+    pub fn new(seed: u32) -> Self {
+        Self { seed, ticks: Vec::new(), checksum: None }
+    }
+    
+    // Reads a replay written by save:
+    //     {"seed": 12345, "checksum": 987654321,
+    //      "ticks": [[120, []], [30, ["move_right"]], [1, ["attack", "move_right"]]]}
+    // Ticks are stored as runs of [count, held actions]
+    pub fn load(path: &str) -> Option<Self> {
+        let data: Value = match std::fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("Failed to parse JSON {}: {}", path, e);
+                    return None;
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to read replay {}: {}", path, e);
+                return None;
+            }
+        };
+        
+        let seed = match data["seed"].as_u64() {
+            Some(seed) => seed as u32,
+            None => {
+                eprintln!("Replay {} has no seed", path);
+                return None;
+            }
+        };
+        
+        let mut ticks = Vec::new();
+        for run in data["ticks"].as_array().into_iter().flatten() {
+            let count = run[0].as_u64().unwrap_or(0) as usize;
+            let actions: Vec<String> = run[1].as_array().into_iter().flatten()
+                .filter_map(|action| action.as_str().map(|a| a.to_string()))
+                .collect();
+            ticks.extend(std::iter::repeat_n(actions, count));
+        }
+        
+        Some(Replay { seed, ticks, checksum: data["checksum"].as_u64() })
+    }
+    
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut runs: Vec<(usize, &Vec<String>)> = Vec::new();
+        for actions in &self.ticks {
+            match runs.last_mut() {
+                Some((count, last)) if *last == actions => *count += 1,
+                _ => runs.push((1, actions)),
+            }
+        }
+        
+        let data = json!({
+            "seed": self.seed,
+            "checksum": self.checksum,
+            "ticks": runs.iter().map(|(count, actions)| json!([count, actions])).collect::<Vec<Value>>(),
+        });
+        let text = serde_json::to_string(&data).expect("replay serializes");
+        std::fs::write(path, text)
+    }
+    
+    // Call once per tick with the input the tick is about to use
+    pub fn record_tick(&mut self, input: &Input) {
+        self.ticks.push(input.held_actions());
+    }
+    
+    pub fn tick_count(&self) -> usize {
+        self.ticks.len()
+    }
+    
+    pub fn held_on(&self, tick: usize) -> HashSet<String> {
+        self.ticks[tick].iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Replay;
+    use crate::game::Game;
+    use crate::renderer::Renderer;
+    
+    // Walks both players around, pushes bugs and attacks. A changed checksum means fixed_update
+    // no longer plays old recordings back the same way
+    #[test]
+    fn walkabout_fixture_ends_on_its_checksum() {
+        let replay = Replay::load("assets/replays/walkabout.json").expect("replay fixture");
+        let mut renderer = Renderer::headless(800, 600);
+        let mut game = Game::new(&mut renderer, 800, 600, replay.seed);
+        
+        let checksum = game.play_replay(&mut renderer, &replay);
+        assert_eq!(Some(checksum), replay.checksum);
+    }
+}
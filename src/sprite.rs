@@ -1,151 +1,293 @@
-use image::GenericImageView;
-
-#[derive(Clone)]
-pub struct Sprite {
-    pub(crate) width: u32,
-    pub(crate) height: u32,
-    pub(crate) pixels: Vec<u32>,
-}
-
-impl Sprite {
-    pub fn load(path: &str) -> Self {
-        let img = match image::open(path) {
-            Ok(img) => img,
-            Err(_) => image::open("assets/sprites/missing_texture.png").unwrap_or_else(|_| {
-                image::DynamicImage::new_rgba8(16, 16)
-            }),
-        };
-        
-        let (width, height) = img.dimensions();
-        let mut pixels = Vec::with_capacity((width * height) as usize);
-        
-        for pixel in img.pixels() {
-            let (_, _, p) = pixel;
-            let r = p[0];
-            let g = p[1];
-            let b = p[2];
-            let a = p[3];
-            pixels.push(((a as u32) << 24) | ((r as u32) << 16) | ((g as u32) << 8) | b as u32);
-        }
-        
-        Sprite { width, height, pixels }
-    }
-    
-    pub fn width(&self) -> u32 { self.width }
-    pub fn height(&self) -> u32 { self.height }
-    
-    pub fn scale(&self, scale: u32) -> Self {
-        if scale == 1 { return self.clone(); }
-        
-        let new_width = self.width * scale;
-        let new_height = self.height * scale;
-        let mut pixels = vec![0; (new_width * new_height) as usize];
-        let old_pixels = &self.pixels;
-        let old_width = self.width;
-        
-        for (idx, &color) in old_pixels.iter().enumerate() {
-            if (color >> 24) == 0 { continue; }
-            let x = (idx as u32) % old_width;
-            let y = (idx as u32) / old_width;
-            let base_y = y * scale * new_width;
-            
-            for dy in 0..scale {
-                let row_start = (base_y + dy * new_width + x * scale) as usize;
-                for dx in 0..scale {
-                    pixels[row_start + dx as usize] = color;
-                }
-            }
-        }
-        
-        Sprite { width: new_width, height: new_height, pixels }
-    }
-    
-    pub fn draw(&self, buffer: &mut [u32], buf_w: usize, buf_h: usize, cx: i32, cy: i32) {
-        let hw = (self.width / 2) as i32;
-        let hh = (self.height / 2) as i32;
-        
-        // Calculate drawing bounds with safety checks
-        let start_y = (cy - hh).max(0) as usize;
-        let end_y = ((cy + hh).min(buf_h as i32 - 1) as usize).min(buf_h);
-        let start_x = (cx - hw).max(0) as i32;
-        let end_x = (cx + hw).min(buf_w as i32 - 1);
-        
-        // Ensure we have valid ranges
-        if start_y >= end_y || start_x >= end_x {
-            return;
-        }
-        
-        for y in start_y..end_y {
-            let src_y = (y as i32 - (cy - hh)) as u32;
-            if src_y >= self.height {
-                continue;
-            }
-            
-            let buf_idx = y * buf_w;
-            let src_row = (src_y * self.width) as usize;
-            
-            for x in start_x..end_x {
-                let src_x = (x - (cx - hw)) as u32;
-                if src_x >= self.width {
-                    continue;
-                }
-                
-                let pixel_idx = src_row + src_x as usize;
-                if pixel_idx < self.pixels.len() {
-                    let color = self.pixels[pixel_idx];
-                    if color >> 24 != 0 {
-                        let buffer_idx = buf_idx + x as usize;
-                        if buffer_idx < buffer.len() {
-                            buffer[buffer_idx] = color;
-                        }
-                    }
-                }
-            }
-        }
-    }
-    
-    pub fn draw_flipped(&self, buffer: &mut [u32], buf_w: usize, buf_h: usize, cx: i32, cy: i32) {
-        let hw = (self.width / 2) as i32;
-        let hh = (self.height / 2) as i32;
-        let flip_offset = self.width as i32 - 1;
-        
-        // Calculate drawing bounds with safety checks
-        let start_y = (cy - hh).max(0) as usize;
-        let end_y = ((cy + hh).min(buf_h as i32 - 1) as usize).min(buf_h);
-        let start_x = (cx - hw).max(0) as i32;
-        let end_x = (cx + hw).min(buf_w as i32 - 1);
-        
-        // Ensure we have valid ranges
-        if start_y >= end_y || start_x >= end_x {
-            return;
-        }
-        
-        for y in start_y..end_y {
-            let src_y = (y as i32 - (cy - hh)) as u32;
-            if src_y >= self.height {
-                continue;
-            }
-            
-            let buf_idx = y * buf_w;
-            let src_row = (src_y * self.width) as usize;
-            
-            for x in start_x..end_x {
-                let src_x = flip_offset - (x - (cx - hw));
-                if src_x < 0 || src_x as u32 >= self.width {
-                    continue;
-                }
-                
-                let pixel_idx = src_row + src_x as usize;
-                if pixel_idx < self.pixels.len() {
-                    let color = self.pixels[pixel_idx];
-                    if color >> 24 != 0 {
-                        let buffer_idx = buf_idx + x as usize;
-                        if buffer_idx < buffer.len() {
-                            buffer[buffer_idx] = color;
-                        }
-                    }
-                }
-            }
-        }
-    }
+use image::GenericImageView;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Alpha-composites an ARGB color over an opaque destination pixel
+#[inline]
+pub fn blend_pixel(dst: u32, src: u32) -> u32 {
+    let alpha = src >> 24;
+    if alpha == 0xFF { return src; }
+    if alpha == 0 { return dst; }
+    
+    let inv = 255 - alpha;
+    let r = (((src >> 16) & 0xFF) * alpha + ((dst >> 16) & 0xFF) * inv) / 255;
+    let g = (((src >> 8) & 0xFF) * alpha + ((dst >> 8) & 0xFF) * inv) / 255;
+    let b = ((src & 0xFF) * alpha + (dst & 0xFF) * inv) / 255;
+    0xFF000000 | (r << 16) | (g << 8) | b
+}
+
+// Named presets used by the JSON settings: "center", "bottom_center", "top_left"
+pub fn named_pivot_point(name: &str, width: u32, height: u32) -> Option<(i32, i32)> {
+    let (w, h) = (width as i32, height as i32);
+    match name {
+        "center" => Some((w / 2, h / 2)),
+        "bottom_center" => Some((w / 2, h)),
+        "top_left" => Some((0, 0)),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
+pub struct Sprite {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    // Shared storage, either this sprite's own image or a larger one such as an atlas page
+    pixels: Rc<Vec<u32>>,
+    // Row length of `pixels` and where this sprite's top-left corner sits inside it
+    stride: u32,
+    origin_x: u32,
+    origin_y: u32,
+    // Point in sprite pixels that lands on the draw position, defaults to the center
+    pub(crate) pivot_x: i32,
+    pub(crate) pivot_y: i32,
+}
+
+impl Sprite {
+    pub fn new(width: u32, height: u32, pixels: Vec<u32>) -> Self {
+        Sprite {
+            width,
+            height,
+            pixels: Rc::new(pixels),
+            stride: width,
+            origin_x: 0,
+            origin_y: 0,
+            pivot_x: (width / 2) as i32,
+            pivot_y: (height / 2) as i32,
+        }
+    }
+    
+    pub fn load(path: &str) -> Self {
+        let img = match image::open(path) {
+            Ok(img) => img,
+            Err(_) => image::open("assets/sprites/missing_texture.png").unwrap_or_else(|_| {
+                image::DynamicImage::new_rgba8(16, 16)
+            }),
+        };
+        
+        let (width, height) = img.dimensions();
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        
+        for pixel in img.pixels() {
+            let (_, _, p) = pixel;
+            let r = p[0];
+            let g = p[1];
+            let b = p[2];
+            let a = p[3];
+            pixels.push(((a as u32) << 24) | ((r as u32) << 16) | ((g as u32) << 8) | b as u32);
+        }
+        
+        Sprite::new(width, height, pixels)
+    }
+    
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    
+    pub fn pixel(&self, x: u32, y: u32) -> u32 {
+        self.row(y)[x as usize]
+    }
+    
+    #[inline]
+    fn row(&self, y: u32) -> &[u32] {
+        let start = ((self.origin_y + y) * self.stride + self.origin_x) as usize;
+        &self.pixels[start..start + self.width as usize]
+    }
+    
+    pub fn set_pivot(&mut self, x: i32, y: i32) {
+        self.pivot_x = x;
+        self.pivot_y = y;
+    }
+    
+    pub fn with_pivot(mut self, x: i32, y: i32) -> Self {
+        self.set_pivot(x, y);
+        self
+    }
+    
+    pub fn with_named_pivot(self, name: &str) -> Self {
+        match named_pivot_point(name, self.width, self.height) {
+            Some((x, y)) => self.with_pivot(x, y),
+            None => {
+                eprintln!("Unknown pivot preset: {}", name);
+                self
+            }
+        }
+    }
+    
+    // Offset from the pivot to the middle of the image, handy for lining colliders up with the art
+    pub fn center_offset(&self) -> (i32, i32) {
+        (self.width as i32 / 2 - self.pivot_x, self.height as i32 / 2 - self.pivot_y)
+    }
+    
+    // Bounding box (x, y, width, height) of the non-transparent pixels
+    pub fn opaque_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+        
+        for y in 0..self.height {
+            for (x, &color) in self.row(y).iter().enumerate() {
+                if color >> 24 == 0 { continue; }
+                min_x = min_x.min(x as u32);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x as u32);
+                max_y = max_y.max(y);
+            }
+        }
+        
+        if min_x > max_x {
+            return None;
+        }
+        Some((min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+    }
+    
+    // Rectangle of this sprite. Inside the image it shares the pixels instead of copying them,
+    // parts hanging over the edge force a copy and come out transparent
+    pub fn region(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        if x + width <= self.width && y + height <= self.height {
+            return Sprite {
+                width,
+                height,
+                pixels: Rc::clone(&self.pixels),
+                stride: self.stride,
+                origin_x: self.origin_x + x,
+                origin_y: self.origin_y + y,
+                pivot_x: (width / 2) as i32,
+                pivot_y: (height / 2) as i32,
+            };
+        }
+        
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        
+        for row in 0..height {
+            let src_y = y + row;
+            for col in 0..width {
+                let src_x = x + col;
+                let color = if src_x < self.width && src_y < self.height {
+                    self.row(src_y)[src_x as usize]
+                } else { 0 };
+                pixels.push(color);
+            }
+        }
+        
+        Sprite::new(width, height, pixels)
+    }
+    
+    // Replaces colors through an RGB to RGB map, keeping each pixel's own alpha
+    pub fn recolor(&self, map: &HashMap<u32, u32>) -> Self {
+        let mut pixels = Vec::with_capacity((self.width * self.height) as usize);
+        for y in 0..self.height {
+            pixels.extend(self.row(y).iter().map(|&color| {
+                match map.get(&(color & 0x00FFFFFF)) {
+                    Some(&to) if color >> 24 != 0 => (color & 0xFF000000) | to,
+                    _ => color,
+                }
+            }));
+        }
+        Sprite::new(self.width, self.height, pixels).with_pivot(self.pivot_x, self.pivot_y)
+    }
+    
+    pub fn scale(&self, scale: u32) -> Self {
+        if scale == 1 { return self.clone(); }
+        
+        let new_width = self.width * scale;
+        let new_height = self.height * scale;
+        let mut pixels = vec![0; (new_width * new_height) as usize];
+        
+        for y in 0..self.height {
+            let base_y = y * scale * new_width;
+            
+            for (x, &color) in self.row(y).iter().enumerate() {
+                if (color >> 24) == 0 { continue; }
+                
+                for dy in 0..scale {
+                    let row_start = (base_y + dy * new_width + x as u32 * scale) as usize;
+                    for dx in 0..scale {
+                        pixels[row_start + dx as usize] = color;
+                    }
+                }
+            }
+        }
+        
+        Sprite {
+            width: new_width,
+            height: new_height,
+            pixels: Rc::new(pixels),
+            stride: new_width,
+            origin_x: 0,
+            origin_y: 0,
+            pivot_x: self.pivot_x * scale as i32,
+            pivot_y: self.pivot_y * scale as i32,
+        }
+    }
+    
+    // Whether drawing at (cx, cy) would put a visible pixel on (x, y), for picking by shape
+    pub fn covers(&self, cx: i32, cy: i32, x: i32, y: i32, flip_horizontal: bool) -> bool {
+        let pivot_x = if flip_horizontal { self.width as i32 - self.pivot_x } else { self.pivot_x };
+        let local_x = x - (cx - pivot_x);
+        let local_y = y - (cy - self.pivot_y);
+        if local_x < 0 || local_y < 0 || local_x >= self.width as i32 || local_y >= self.height as i32 {
+            return false;
+        }
+        
+        let src_x = if flip_horizontal { self.width as i32 - 1 - local_x } else { local_x };
+        self.pixel(src_x as u32, local_y as u32) >> 24 != 0
+    }
+    
+    pub fn draw(&self, buffer: &mut [u32], buf_w: usize, buf_h: usize, cx: i32, cy: i32) {
+        self.draw_at(buffer, buf_w, buf_h, cx - self.pivot_x, cy - self.pivot_y, false);
+    }
+    
+    pub fn draw_flipped(&self, buffer: &mut [u32], buf_w: usize, buf_h: usize, cx: i32, cy: i32) {
+        // Mirror the pivot as well so flipping turns the sprite around its anchor instead of shifting it
+        let pivot_x = self.width as i32 - self.pivot_x;
+        self.draw_at(buffer, buf_w, buf_h, cx - pivot_x, cy - self.pivot_y, true);
+    }
+    
+    pub fn draw_at(&self, buffer: &mut [u32], buf_w: usize, buf_h: usize, left: i32, top: i32, flip_horizontal: bool) {
+        let start_x = left.max(0);
+        let end_x = (left + self.width as i32).min(buf_w as i32);
+        let start_y = top.max(0);
+        let end_y = (top + self.height as i32).min(buf_h as i32);
+        
+        if start_x >= end_x || start_y >= end_y {
+            return;
+        }
+        
+        for y in start_y..end_y {
+            let src_row = self.row((y - top) as u32);
+            let buf_row = y as usize * buf_w;
+            
+            for x in start_x..end_x {
+                let src_x = if flip_horizontal { self.width as i32 - 1 - (x - left) } else { x - left };
+                let color = src_row[src_x as usize];
+                if color >> 24 != 0 {
+                    buffer[buf_row + x as usize] = color;
+                }
+            }
+        }
+    }
+    
+    pub fn draw_with_alpha(&self, buffer: &mut [u32], buf_w: usize, buf_h: usize, cx: i32, cy: i32, alpha: u32) {
+        let left = cx - self.pivot_x;
+        let top = cy - self.pivot_y;
+        
+        for src_y in 0..self.height as i32 {
+            let y = top + src_y;
+            if y < 0 || y >= buf_h as i32 {
+                continue;
+            }
+            
+            let src_row = self.row(src_y as u32);
+            for src_x in 0..self.width as i32 {
+                let x = left + src_x;
+                if x < 0 || x >= buf_w as i32 {
+                    continue;
+                }
+                
+                let color = src_row[src_x as usize];
+                let pixel_alpha = (color >> 24) * alpha / 255;
+                if pixel_alpha != 0 {
+                    let idx = y as usize * buf_w + x as usize;
+                    buffer[idx] = blend_pixel(buffer[idx], (pixel_alpha << 24) | (color & 0x00FFFFFF));
+                }
+            }
+        }
+    }
 }
\ No newline at end of file